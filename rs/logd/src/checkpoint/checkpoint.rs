@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, Metadata};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use super::CheckpointError;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub path: PathBuf,
+    pub dev: u64,
+    pub ino: u64,
+    pub offset: u64,
+}
+
+impl Checkpoint {
+    pub fn new(path: &Path, metadata: &Metadata, offset: u64) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            offset,
+        }
+    }
+
+    // true if the checkpoint was taken for the same file (device + inode)
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        self.dev == metadata.dev() && self.ino == metadata.ino()
+    }
+}
+
+struct State {
+    checkpoints: HashMap<PathBuf, Checkpoint>,
+    dirty: bool,
+    last_flush: Instant,
+}
+
+#[derive(Clone)]
+pub struct CheckpointStore {
+    path: PathBuf,
+    flush_interval: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+impl CheckpointStore {
+    pub fn load(path: &Path, flush_interval: Duration) -> Result<Self, CheckpointError> {
+        let checkpoints = match File::open(path) {
+            Ok(file) => match serde_json::from_reader::<_, Vec<Checkpoint>>(BufReader::new(file)) {
+                Ok(checkpoints) => checkpoints
+                    .into_iter()
+                    .map(|checkpoint| (checkpoint.path.clone(), checkpoint))
+                    .collect(),
+                Err(e) => {
+                    warn!("Discarding unreadable checkpoints {}: {e}", path.display());
+                    HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        info!(
+            "Loaded {} checkpoints from {}",
+            checkpoints.len(),
+            path.display()
+        );
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            flush_interval,
+            state: Arc::new(Mutex::new(State {
+                checkpoints,
+                dirty: false,
+                last_flush: Instant::now(),
+            })),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Checkpoint> {
+        self.lock().checkpoints.get(path).cloned()
+    }
+
+    // Checkpoint of the same file (device + inode) stored under a different path
+    pub fn find_moved(&self, path: &Path, metadata: &Metadata) -> Option<Checkpoint> {
+        self.lock()
+            .checkpoints
+            .values()
+            .find(|checkpoint| checkpoint.path != path && checkpoint.matches(metadata))
+            .cloned()
+    }
+
+    pub fn remove(&self, path: &Path) {
+        let mut state = self.lock();
+        if state.checkpoints.remove(path).is_some() {
+            state.dirty = true;
+        }
+    }
+
+    // Keeps the checkpoints whose path matches
+    pub fn retain(&self, mut f: impl FnMut(&Path) -> bool) {
+        let mut state = self.lock();
+        let len = state.checkpoints.len();
+        state.checkpoints.retain(|path, _| f(path));
+        if state.checkpoints.len() != len {
+            info!("Removed {} checkpoints", len - state.checkpoints.len());
+            state.dirty = true;
+        }
+    }
+
+    pub fn commit(&self, path: &Path, metadata: &Metadata, offset: u64) {
+        let mut state = self.lock();
+        state
+            .checkpoints
+            .insert(path.to_path_buf(), Checkpoint::new(path, metadata, offset));
+        state.dirty = true;
+    }
+
+    pub fn flush_if_due(&self) -> Result<(), CheckpointError> {
+        if self.lock().last_flush.elapsed() < self.flush_interval {
+            return Ok(());
+        }
+        self.flush()
+    }
+
+    // Writes to a temporary file and renames it, so a crash never leaves a partial file
+    pub fn flush(&self) -> Result<(), CheckpointError> {
+        let checkpoints: Vec<Checkpoint> = {
+            let mut state = self.lock();
+            state.last_flush = Instant::now();
+            if !state.dirty {
+                return Ok(());
+            }
+            state.dirty = false;
+            state.checkpoints.values().cloned().collect()
+        };
+
+        let result = self.write(&checkpoints);
+        if result.is_err() {
+            // retry on the next flush
+            self.lock().dirty = true;
+        }
+        result
+    }
+
+    fn write(&self, checkpoints: &[Checkpoint]) -> Result<(), CheckpointError> {
+        let parent = self.path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(parent)?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        let file = File::create(&tmp_path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, checkpoints)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        File::open(parent)?.sync_all()?;
+
+        debug!(
+            "Persisted {} checkpoints to {}",
+            checkpoints.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
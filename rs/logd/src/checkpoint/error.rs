@@ -0,0 +1,10 @@
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CheckpointError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Json serialize error: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,6 @@
+mod checkpoint;
+mod error;
+mod test;
+
+pub use checkpoint::{Checkpoint, CheckpointStore};
+pub use error::CheckpointError;
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use std::fs::metadata;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    use crate::checkpoint::{CheckpointError, CheckpointStore};
+    use crate::util::test::test_util::create_test_file;
+
+    #[test]
+    fn test_checkpoint_store_roundtrip() -> Result<(), CheckpointError> {
+        let temp_dir = tempdir()?;
+        let store_path = temp_dir.path().join("state").join("checkpoints.json");
+        let file_path = create_test_file(temp_dir.path(), "0.log")?;
+        let file_metadata = metadata(&file_path)?;
+
+        let store = CheckpointStore::load(&store_path, Duration::from_secs(60))?;
+        assert!(store.get(&file_path).is_none());
+
+        store.commit(&file_path, &file_metadata, 42);
+        store.flush()?;
+        assert!(store_path.exists());
+        assert!(!store_path.with_extension("tmp").exists());
+
+        let reloaded = CheckpointStore::load(&store_path, Duration::from_secs(60))?;
+        let checkpoint = reloaded.get(&file_path).unwrap();
+        assert_eq!(checkpoint.offset, 42);
+        assert!(checkpoint.matches(&file_metadata));
+        assert_eq!(reloaded.get(&file_path), store.get(&file_path));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_store_tracks_inode() -> Result<(), CheckpointError> {
+        let temp_dir = tempdir()?;
+        let store_path = temp_dir.path().join("checkpoints.json");
+        let file_path = create_test_file(temp_dir.path(), "0.log")?;
+
+        let store = CheckpointStore::load(&store_path, Duration::from_secs(60))?;
+        store.commit(&file_path, &metadata(&file_path)?, 42);
+
+        // A renamed file is found by its inode
+        let rotated_path = temp_dir.path().join("0.log.20240101-000000");
+        std::fs::rename(&file_path, &rotated_path)?;
+        let moved = store.find_moved(&rotated_path, &metadata(&rotated_path)?);
+        assert_eq!(moved.map(|c| c.path), Some(file_path.clone()));
+
+        // A new file under the same name does not match the checkpoint
+        create_test_file(temp_dir.path(), "0.log")?;
+        assert!(!store
+            .get(&file_path)
+            .unwrap()
+            .matches(&metadata(&file_path)?));
+        assert!(store
+            .find_moved(&file_path, &metadata(&file_path)?)
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_store_flush_if_due() -> Result<(), CheckpointError> {
+        let temp_dir = tempdir()?;
+        let store_path = temp_dir.path().join("checkpoints.json");
+        let file_path = create_test_file(temp_dir.path(), "0.log")?;
+
+        let store = CheckpointStore::load(&store_path, Duration::from_secs(60))?;
+        store.commit(&file_path, &metadata(&file_path)?, 1);
+        store.flush_if_due()?;
+        assert!(!store_path.exists());
+
+        let store = CheckpointStore::load(&store_path, Duration::ZERO)?;
+        store.commit(&file_path, &metadata(&file_path)?, 1);
+        store.flush_if_due()?;
+        assert!(store_path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_store_discards_corrupt_file() -> Result<(), CheckpointError> {
+        let temp_dir = tempdir()?;
+        let store_path = temp_dir.path().join("checkpoints.json");
+        std::fs::write(&store_path, "{not json")?;
+
+        let store = CheckpointStore::load(&store_path, Duration::from_secs(60))?;
+        assert!(store.get(&store_path).is_none());
+        Ok(())
+    }
+}
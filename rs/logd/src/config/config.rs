@@ -0,0 +1,526 @@
+use serde::Deserialize;
+use shared::env::get_env_var;
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::constant::{
+    BATCH_SIZE, CHECKPOINT_PATH, DEDUP_WINDOW, ENV_BATCH_SIZE, ENV_CHECKPOINT_PATH,
+    ENV_COMPRESSION, ENV_CONFIG, ENV_CONTAINER_RATE_LIMIT_BYTES, ENV_CONTAINER_RATE_LIMIT_LINES,
+    ENV_DEDUP, ENV_DEDUP_WINDOW_MS, ENV_EXCLUDE, ENV_HEALTH_PORT, ENV_INCLUDE,
+    ENV_INOTIFY_SLEEP_MS, ENV_LOG_PATH, ENV_MAX_INFLIGHT_BYTES, ENV_MULTILINE,
+    ENV_MULTILINE_FLUSH_TIMEOUT_MS, ENV_MULTILINE_START_PATTERN, ENV_OUTPUT_FORMAT,
+    ENV_POD_ANNOTATIONS, ENV_POD_ENRICHMENT, ENV_POLL_INTERVAL_MS, ENV_RATE_LIMIT_BYTES,
+    ENV_RATE_LIMIT_LINES, ENV_RECV_TIMEOUT_MS, ENV_REDACT, ENV_REDACT_RULES, ENV_ROUTE,
+    ENV_SHIP_COMPRESSED_LOGS, ENV_SHUTDOWN_GRACE_PERIOD_SECS, ENV_SPOOL_MAX_AGE_SECS,
+    ENV_SPOOL_MAX_BYTES, ENV_SPOOL_PATH, ENV_WATCH_MODE, HEALTH_PORT, HIK8S_ROUTE_LOG,
+    INOTIFY_SLEEP, LOG_PATH, MAX_INFLIGHT_BYTES, MULTILINE_FLUSH_TIMEOUT, POLL_INTERVAL,
+    RECV_TIMEOUT, SHUTDOWN_GRACE_PERIOD, SPOOL_MAX_AGE, SPOOL_MAX_BYTES,
+};
+use crate::threads::read_and_send::RedactionRuleConfig;
+
+use super::ConfigError;
+
+const BATCH_SIZE_RANGE: RangeInclusive<usize> = 4096..=64 * 1024 * 1024;
+const MAX_INFLIGHT_BYTES_RANGE: RangeInclusive<usize> = 4096..=u32::MAX as usize;
+const RECV_TIMEOUT_MS_RANGE: RangeInclusive<u64> = 10..=60_000;
+const INOTIFY_SLEEP_MS_RANGE: RangeInclusive<u64> = 1..=10_000;
+const MULTILINE_FLUSH_TIMEOUT_MS_RANGE: RangeInclusive<u64> = 10..=600_000;
+const SPOOL_MAX_BYTES_RANGE: RangeInclusive<u64> = 1024 * 1024..=1024 * 1024 * 1024 * 1024;
+const SPOOL_MAX_AGE_SECS_RANGE: RangeInclusive<u64> = 60..=30 * 24 * 60 * 60;
+const DEDUP_WINDOW_MS_RANGE: RangeInclusive<u64> = 1..=3_600_000;
+const POLL_INTERVAL_MS_RANGE: RangeInclusive<u64> = 10..=600_000;
+const SHUTDOWN_GRACE_PERIOD_SECS_RANGE: RangeInclusive<u64> = 0..=3600;
+
+// Settings read from the file in LOGD_CONFIG, each can be overridden by an env var
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Directory with container logs
+    pub log_path: PathBuf,
+    // File with the offsets of the shipped files
+    pub checkpoint_path: PathBuf,
+    // Route of the upload endpoint
+    pub route: String,
+    // Bytes read from a file per upload
+    pub batch_size: usize,
+    // Bytes read and not yet uploaded or spooled
+    pub max_inflight_bytes: usize,
+    // Time the read thread waits for file events before it retries and flushes
+    pub recv_timeout_ms: u64,
+    // Time the file events thread sleeps when inotify has no events
+    pub inotify_sleep_ms: u64,
+    // Decompress and ship rotated .gz files that were not fully shipped before
+    pub ship_compressed_logs: bool,
+    // raw or structured
+    pub output_format: Option<String>,
+    // Comma separated multiline presets
+    pub multiline: Option<String>,
+    pub multiline_start_pattern: Option<String>,
+    pub multiline_flush_timeout_ms: u64,
+    // Comma separated rules of the containers to ship and to skip
+    pub include: String,
+    pub exclude: String,
+    // Comma separated built-in redaction rules, or all
+    pub redact: String,
+    pub redact_rules: Vec<RedactionRuleConfig>,
+    // none, gzip or zstd
+    pub compression: Option<String>,
+    pub pod_enrichment: bool,
+    pub pod_annotations: bool,
+    // Chunks that fail to upload are spooled in this directory if set
+    pub spool_path: Option<PathBuf>,
+    pub spool_max_bytes: u64,
+    pub spool_max_age_secs: u64,
+    // Per second, for the node and for each container
+    pub rate_limit_bytes: Option<f64>,
+    pub rate_limit_lines: Option<f64>,
+    pub container_rate_limit_bytes: Option<f64>,
+    pub container_rate_limit_lines: Option<f64>,
+    // exact or similar
+    pub dedup: Option<String>,
+    pub dedup_window_ms: u64,
+    // auto, inotify or poll
+    pub watch_mode: Option<String>,
+    pub poll_interval_ms: u64,
+    pub shutdown_grace_period_secs: u64,
+    // Port of the probe and metrics endpoints
+    pub health_port: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            log_path: PathBuf::from(LOG_PATH),
+            checkpoint_path: PathBuf::from(CHECKPOINT_PATH),
+            route: HIK8S_ROUTE_LOG.to_string(),
+            batch_size: BATCH_SIZE,
+            max_inflight_bytes: MAX_INFLIGHT_BYTES,
+            recv_timeout_ms: RECV_TIMEOUT.as_millis() as u64,
+            inotify_sleep_ms: INOTIFY_SLEEP.as_millis() as u64,
+            ship_compressed_logs: false,
+            output_format: None,
+            multiline: None,
+            multiline_start_pattern: None,
+            multiline_flush_timeout_ms: MULTILINE_FLUSH_TIMEOUT.as_millis() as u64,
+            include: String::new(),
+            exclude: String::new(),
+            redact: String::new(),
+            redact_rules: Vec::new(),
+            compression: None,
+            pod_enrichment: false,
+            pod_annotations: false,
+            spool_path: None,
+            spool_max_bytes: SPOOL_MAX_BYTES,
+            spool_max_age_secs: SPOOL_MAX_AGE.as_secs(),
+            rate_limit_bytes: None,
+            rate_limit_lines: None,
+            container_rate_limit_bytes: None,
+            container_rate_limit_lines: None,
+            dedup: None,
+            dedup_window_ms: DEDUP_WINDOW.as_millis() as u64,
+            watch_mode: None,
+            poll_interval_ms: POLL_INTERVAL.as_millis() as u64,
+            shutdown_grace_period_secs: SHUTDOWN_GRACE_PERIOD.as_secs(),
+            health_port: HEALTH_PORT,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, ConfigError> {
+        let config = match get_env_var(ENV_CONFIG) {
+            Ok(path) => Self::from_file(Path::new(&path))?,
+            Err(_) => Self::default(),
+        };
+        config
+            .with_overrides(|key| get_env_var(key).ok())?
+            .validate()
+    }
+
+    // The format follows the extension
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") if content.trim().is_empty() => Ok(Self::default()),
+            Some("yaml" | "yml") => {
+                serde_yaml::from_str(&content).map_err(|e| ConfigError::Yaml(path.to_path_buf(), e))
+            }
+            Some("toml") => {
+                toml::from_str(&content).map_err(|e| ConfigError::Toml(path.to_path_buf(), e))
+            }
+            _ => Err(ConfigError::Format(path.to_path_buf())),
+        }
+    }
+
+    // Values of the env vars that are set replace the values of the file
+    pub fn with_overrides(
+        mut self,
+        get: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        set(&mut self.log_path, "log_path", ENV_LOG_PATH, &get)?;
+        set(
+            &mut self.checkpoint_path,
+            "checkpoint_path",
+            ENV_CHECKPOINT_PATH,
+            &get,
+        )?;
+        set(&mut self.route, "route", ENV_ROUTE, &get)?;
+        set(&mut self.batch_size, "batch_size", ENV_BATCH_SIZE, &get)?;
+        set(
+            &mut self.max_inflight_bytes,
+            "max_inflight_bytes",
+            ENV_MAX_INFLIGHT_BYTES,
+            &get,
+        )?;
+        set(
+            &mut self.recv_timeout_ms,
+            "recv_timeout_ms",
+            ENV_RECV_TIMEOUT_MS,
+            &get,
+        )?;
+        set(
+            &mut self.inotify_sleep_ms,
+            "inotify_sleep_ms",
+            ENV_INOTIFY_SLEEP_MS,
+            &get,
+        )?;
+        set_flag(
+            &mut self.ship_compressed_logs,
+            "ship_compressed_logs",
+            ENV_SHIP_COMPRESSED_LOGS,
+            &get,
+        )?;
+        set_some(
+            &mut self.output_format,
+            "output_format",
+            ENV_OUTPUT_FORMAT,
+            &get,
+        )?;
+        set_some(&mut self.multiline, "multiline", ENV_MULTILINE, &get)?;
+        set_some(
+            &mut self.multiline_start_pattern,
+            "multiline_start_pattern",
+            ENV_MULTILINE_START_PATTERN,
+            &get,
+        )?;
+        set(
+            &mut self.multiline_flush_timeout_ms,
+            "multiline_flush_timeout_ms",
+            ENV_MULTILINE_FLUSH_TIMEOUT_MS,
+            &get,
+        )?;
+        set(&mut self.include, "include", ENV_INCLUDE, &get)?;
+        set(&mut self.exclude, "exclude", ENV_EXCLUDE, &get)?;
+        set(&mut self.redact, "redact", ENV_REDACT, &get)?;
+        // LOGD_REDACT_RULES='[{"name": "token", "pattern": "token=\\w+", "replacement": "token=***"}]'
+        if let Some(value) = get(ENV_REDACT_RULES) {
+            self.redact_rules = serde_json::from_str(&value).map_err(|e| {
+                ConfigError::Invalid("redact_rules", ENV_REDACT_RULES, e.to_string())
+            })?;
+        }
+        set_some(&mut self.compression, "compression", ENV_COMPRESSION, &get)?;
+        set_flag(
+            &mut self.pod_enrichment,
+            "pod_enrichment",
+            ENV_POD_ENRICHMENT,
+            &get,
+        )?;
+        set_flag(
+            &mut self.pod_annotations,
+            "pod_annotations",
+            ENV_POD_ANNOTATIONS,
+            &get,
+        )?;
+        set_some(&mut self.spool_path, "spool_path", ENV_SPOOL_PATH, &get)?;
+        set(
+            &mut self.spool_max_bytes,
+            "spool_max_bytes",
+            ENV_SPOOL_MAX_BYTES,
+            &get,
+        )?;
+        set(
+            &mut self.spool_max_age_secs,
+            "spool_max_age_secs",
+            ENV_SPOOL_MAX_AGE_SECS,
+            &get,
+        )?;
+        set_some(
+            &mut self.rate_limit_bytes,
+            "rate_limit_bytes",
+            ENV_RATE_LIMIT_BYTES,
+            &get,
+        )?;
+        set_some(
+            &mut self.rate_limit_lines,
+            "rate_limit_lines",
+            ENV_RATE_LIMIT_LINES,
+            &get,
+        )?;
+        set_some(
+            &mut self.container_rate_limit_bytes,
+            "container_rate_limit_bytes",
+            ENV_CONTAINER_RATE_LIMIT_BYTES,
+            &get,
+        )?;
+        set_some(
+            &mut self.container_rate_limit_lines,
+            "container_rate_limit_lines",
+            ENV_CONTAINER_RATE_LIMIT_LINES,
+            &get,
+        )?;
+        set_some(&mut self.dedup, "dedup", ENV_DEDUP, &get)?;
+        set(
+            &mut self.dedup_window_ms,
+            "dedup_window_ms",
+            ENV_DEDUP_WINDOW_MS,
+            &get,
+        )?;
+        set_some(&mut self.watch_mode, "watch_mode", ENV_WATCH_MODE, &get)?;
+        set(
+            &mut self.poll_interval_ms,
+            "poll_interval_ms",
+            ENV_POLL_INTERVAL_MS,
+            &get,
+        )?;
+        set(
+            &mut self.shutdown_grace_period_secs,
+            "shutdown_grace_period_secs",
+            ENV_SHUTDOWN_GRACE_PERIOD_SECS,
+            &get,
+        )?;
+        set(&mut self.health_port, "health_port", ENV_HEALTH_PORT, &get)?;
+        Ok(self)
+    }
+
+    pub fn validate(self) -> Result<Self, ConfigError> {
+        for (key, env, path) in [
+            ("log_path", ENV_LOG_PATH, &self.log_path),
+            (
+                "checkpoint_path",
+                ENV_CHECKPOINT_PATH,
+                &self.checkpoint_path,
+            ),
+        ] {
+            if !path.is_absolute() {
+                return Err(ConfigError::Invalid(
+                    key,
+                    env,
+                    format!("must be an absolute path, got {path:?}"),
+                ));
+            }
+        }
+        let route = self.route.trim_matches('/');
+        if route.is_empty() || route.contains(char::is_whitespace) {
+            return Err(ConfigError::Invalid(
+                "route",
+                ENV_ROUTE,
+                format!("must be a path without whitespace, got {:?}", self.route),
+            ));
+        }
+        check_range(
+            "batch_size",
+            ENV_BATCH_SIZE,
+            self.batch_size,
+            BATCH_SIZE_RANGE,
+        )?;
+        check_range(
+            "max_inflight_bytes",
+            ENV_MAX_INFLIGHT_BYTES,
+            self.max_inflight_bytes,
+            MAX_INFLIGHT_BYTES_RANGE,
+        )?;
+        for (key, env, value, range) in [
+            (
+                "recv_timeout_ms",
+                ENV_RECV_TIMEOUT_MS,
+                self.recv_timeout_ms,
+                RECV_TIMEOUT_MS_RANGE,
+            ),
+            (
+                "inotify_sleep_ms",
+                ENV_INOTIFY_SLEEP_MS,
+                self.inotify_sleep_ms,
+                INOTIFY_SLEEP_MS_RANGE,
+            ),
+            (
+                "multiline_flush_timeout_ms",
+                ENV_MULTILINE_FLUSH_TIMEOUT_MS,
+                self.multiline_flush_timeout_ms,
+                MULTILINE_FLUSH_TIMEOUT_MS_RANGE,
+            ),
+            (
+                "spool_max_bytes",
+                ENV_SPOOL_MAX_BYTES,
+                self.spool_max_bytes,
+                SPOOL_MAX_BYTES_RANGE,
+            ),
+            (
+                "spool_max_age_secs",
+                ENV_SPOOL_MAX_AGE_SECS,
+                self.spool_max_age_secs,
+                SPOOL_MAX_AGE_SECS_RANGE,
+            ),
+            (
+                "dedup_window_ms",
+                ENV_DEDUP_WINDOW_MS,
+                self.dedup_window_ms,
+                DEDUP_WINDOW_MS_RANGE,
+            ),
+            (
+                "poll_interval_ms",
+                ENV_POLL_INTERVAL_MS,
+                self.poll_interval_ms,
+                POLL_INTERVAL_MS_RANGE,
+            ),
+            (
+                "shutdown_grace_period_secs",
+                ENV_SHUTDOWN_GRACE_PERIOD_SECS,
+                self.shutdown_grace_period_secs,
+                SHUTDOWN_GRACE_PERIOD_SECS_RANGE,
+            ),
+        ] {
+            check_range(key, env, value, range)?;
+        }
+        for (key, env, rate) in [
+            (
+                "rate_limit_bytes",
+                ENV_RATE_LIMIT_BYTES,
+                self.rate_limit_bytes,
+            ),
+            (
+                "rate_limit_lines",
+                ENV_RATE_LIMIT_LINES,
+                self.rate_limit_lines,
+            ),
+            (
+                "container_rate_limit_bytes",
+                ENV_CONTAINER_RATE_LIMIT_BYTES,
+                self.container_rate_limit_bytes,
+            ),
+            (
+                "container_rate_limit_lines",
+                ENV_CONTAINER_RATE_LIMIT_LINES,
+                self.container_rate_limit_lines,
+            ),
+        ] {
+            if rate.is_some_and(|rate| !(rate.is_finite() && rate > 0.0)) {
+                return Err(ConfigError::Invalid(
+                    key,
+                    env,
+                    format!("must be a positive rate, got {}", rate.unwrap()),
+                ));
+            }
+        }
+        if self.health_port == 0 {
+            return Err(ConfigError::Invalid(
+                "health_port",
+                ENV_HEALTH_PORT,
+                "must be between 1 and 65535, got 0".to_string(),
+            ));
+        }
+        Ok(Self {
+            route: route.to_string(),
+            ..self
+        })
+    }
+
+    pub fn recv_timeout(&self) -> Duration {
+        Duration::from_millis(self.recv_timeout_ms)
+    }
+
+    pub fn inotify_sleep(&self) -> Duration {
+        Duration::from_millis(self.inotify_sleep_ms)
+    }
+}
+
+fn parse<T>(key: &'static str, env: &'static str, value: &str) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| ConfigError::Invalid(key, env, format!("{e}, got {value:?}")))
+}
+
+fn set<T>(
+    target: &mut T,
+    key: &'static str,
+    env: &'static str,
+    get: impl Fn(&str) -> Option<String>,
+) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = get(env) {
+        *target = parse(key, env, &value)?;
+    }
+    Ok(())
+}
+
+fn set_some<T>(
+    target: &mut Option<T>,
+    key: &'static str,
+    env: &'static str,
+    get: impl Fn(&str) -> Option<String>,
+) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Some(value) = get(env) {
+        *target = Some(parse(key, env, &value)?);
+    }
+    Ok(())
+}
+
+fn set_flag(
+    target: &mut bool,
+    key: &'static str,
+    env: &'static str,
+    get: impl Fn(&str) -> Option<String>,
+) -> Result<(), ConfigError> {
+    if let Some(value) = get(env) {
+        *target = match value.trim() {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => {
+                return Err(ConfigError::Invalid(
+                    key,
+                    env,
+                    format!("must be true, false, 1 or 0, got {value:?}"),
+                ))
+            }
+        };
+    }
+    Ok(())
+}
+
+fn check_range<T>(
+    key: &'static str,
+    env: &'static str,
+    value: T,
+    range: RangeInclusive<T>,
+) -> Result<(), ConfigError>
+where
+    T: PartialOrd + Display,
+{
+    if range.contains(&value) {
+        return Ok(());
+    }
+    Err(ConfigError::Invalid(
+        key,
+        env,
+        format!(
+            "must be between {} and {}, got {value}",
+            range.start(),
+            range.end()
+        ),
+    ))
+}
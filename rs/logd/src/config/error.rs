@@ -0,0 +1,17 @@
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("Invalid YAML in config file {0}: {1}")]
+    Yaml(PathBuf, serde_yaml::Error),
+    #[error("Invalid TOML in config file {0}: {1}")]
+    Toml(PathBuf, toml::de::Error),
+    #[error("Unsupported config file {0}, expected a .yaml, .yml or .toml extension")]
+    Format(PathBuf),
+    #[error("Invalid config value {0} ({1}): {2}")]
+    Invalid(&'static str, &'static str, String),
+}
@@ -0,0 +1,6 @@
+mod config;
+mod error;
+mod test;
+
+pub use config::Config;
+pub use error::ConfigError;
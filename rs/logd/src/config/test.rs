@@ -0,0 +1,233 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs::write;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    use crate::config::{Config, ConfigError};
+    use crate::threads::process_file_events::WatchMode;
+    use crate::threads::read_and_send::ReadOptions;
+
+    #[test]
+    fn test_config_from_yaml() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("logd.yaml");
+        write(
+            &path,
+            "log_path: /var/lib/docker/containers\nbatch_size: 65536\ninotify_sleep_ms: 250\n",
+        )
+        .unwrap();
+
+        let config = Config::from_file(&path).unwrap().validate().unwrap();
+        assert_eq!(config.log_path, PathBuf::from("/var/lib/docker/containers"));
+        assert_eq!(config.batch_size, 65536);
+        assert_eq!(config.inotify_sleep(), Duration::from_millis(250));
+        assert_eq!(config.route, Config::default().route);
+        assert_eq!(config.recv_timeout_ms, Config::default().recv_timeout_ms);
+    }
+
+    #[test]
+    fn test_config_from_toml() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("logd.toml");
+        write(&path, "route = \"/log/\"\nrecv_timeout_ms = 1000\n").unwrap();
+
+        let config = Config::from_file(&path).unwrap().validate().unwrap();
+        assert_eq!(config.route, "log");
+        assert_eq!(config.recv_timeout(), Duration::from_secs(1));
+        assert_eq!(config.batch_size, Config::default().batch_size);
+    }
+
+    #[test]
+    fn test_config_env_overrides_file() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("logd.yml");
+        write(&path, "batch_size: 65536\nroute: log\n").unwrap();
+        let env = HashMap::from([("LOGD_BATCH_SIZE", "8192"), ("LOGD_LOG_PATH", "/logs")]);
+
+        let config = Config::from_file(&path)
+            .unwrap()
+            .with_overrides(|key| env.get(key).map(|value| value.to_string()))
+            .unwrap()
+            .validate()
+            .unwrap();
+        assert_eq!(config.batch_size, 8192);
+        assert_eq!(config.log_path, PathBuf::from("/logs"));
+        assert_eq!(config.route, "log");
+    }
+
+    #[test]
+    fn test_config_rejects_invalid_values() {
+        let error = Config::default()
+            .with_overrides(|key| (key == "LOGD_BATCH_SIZE").then(|| "1024".to_string()))
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(matches!(error, ConfigError::Invalid("batch_size", _, _)));
+        assert_eq!(
+            error.to_string(),
+            "Invalid config value batch_size (LOGD_BATCH_SIZE): must be between 4096 and 67108864, got 1024"
+        );
+
+        let error = Config::default()
+            .with_overrides(|key| (key == "LOGD_RECV_TIMEOUT_MS").then(|| "soon".to_string()))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::Invalid("recv_timeout_ms", _, _)
+        ));
+
+        let relative = Config {
+            log_path: PathBuf::from("logs"),
+            ..Config::default()
+        };
+        assert!(matches!(
+            relative.validate(),
+            Err(ConfigError::Invalid("log_path", _, _))
+        ));
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_fields_and_formats() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("logd.yaml");
+        write(&path, "batchsize: 65536\n").unwrap();
+        let error = Config::from_file(&path).unwrap_err();
+        assert!(matches!(error, ConfigError::Yaml(_, _)));
+        assert!(error.to_string().contains("batchsize"));
+
+        let path = temp_dir.path().join("logd.json");
+        write(&path, "{}").unwrap();
+        assert!(matches!(
+            Config::from_file(&path),
+            Err(ConfigError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_options_from_config() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("logd.yaml");
+        write(
+            &path,
+            "checkpoint_path: /data/checkpoints.json\n\
+             output_format: structured\n\
+             multiline: java\n\
+             multiline_flush_timeout_ms: 5000\n\
+             redact_rules:\n  - name: session\n    pattern: session=\\w+\n\
+             spool_path: /data/spool\n\
+             container_rate_limit_lines: 100\n\
+             dedup: exact\n\
+             dedup_window_ms: 30000\n\
+             watch_mode: poll\n",
+        )
+        .unwrap();
+        let env = HashMap::from([
+            ("LOGD_POD_ENRICHMENT", "true"),
+            ("LOGD_SPOOL_MAX_AGE_SECS", "3600"),
+            ("LOGD_POLL_INTERVAL_MS", "250"),
+            ("LOGD_MAX_INFLIGHT_BYTES", "8388608"),
+        ]);
+
+        let config = Config::from_file(&path)
+            .unwrap()
+            .with_overrides(|key| env.get(key).map(|value| value.to_string()))
+            .unwrap()
+            .validate()
+            .unwrap();
+        assert_eq!(
+            config.checkpoint_path,
+            PathBuf::from("/data/checkpoints.json")
+        );
+
+        let options = ReadOptions::from_config(&config).unwrap();
+        assert!(options.multiline.is_some());
+        assert_eq!(options.flush_timeout, Duration::from_secs(5));
+        assert!(!options.redactor.is_empty());
+        assert!(options.pod_enrichment);
+        let spool = options.spool.unwrap();
+        assert_eq!(spool.path, PathBuf::from("/data/spool"));
+        assert_eq!(spool.max_age, Duration::from_secs(3600));
+        assert_eq!(options.rate_limits.container.lines_per_sec, Some(100.0));
+        assert_eq!(options.rate_limits.node.lines_per_sec, None);
+        assert_eq!(options.dedup.unwrap().window, Duration::from_secs(30));
+        assert_eq!(options.watch.mode, WatchMode::Poll);
+        assert_eq!(options.watch.poll_interval, Duration::from_millis(250));
+        assert_eq!(options.budget.capacity(), 8 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_read_options_reject_invalid_values() {
+        let with_env = |key: &'static str, value: &'static str| {
+            Config::default().with_overrides(move |env| (env == key).then(|| value.to_string()))
+        };
+
+        let error = with_env("LOGD_CONTAINER_RATE_LIMIT_LINES", "0")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::Invalid("container_rate_limit_lines", _, _)
+        ));
+        let error = with_env("LOGD_SPOOL_MAX_BYTES", "1024")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::Invalid("spool_max_bytes", _, _)
+        ));
+        let error = with_env("LOGD_MAX_INFLIGHT_BYTES", "100")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::Invalid("max_inflight_bytes", _, _)
+        ));
+        let error = with_env("LOGD_CHECKPOINT_PATH", "checkpoints.json")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::Invalid("checkpoint_path", _, _)
+        ));
+        assert!(matches!(
+            with_env("LOGD_SHIP_COMPRESSED_LOGS", "yes"),
+            Err(ConfigError::Invalid("ship_compressed_logs", _, _))
+        ));
+        assert!(matches!(
+            with_env("LOGD_HEALTH_PORT", "80800"),
+            Err(ConfigError::Invalid("health_port", _, _))
+        ));
+        let error = with_env("LOGD_HEALTH_PORT", "0")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(matches!(error, ConfigError::Invalid("health_port", _, _)));
+        assert!(matches!(
+            with_env("LOGD_REDACT_RULES", "[{\"name\": \"x\"}]"),
+            Err(ConfigError::Invalid("redact_rules", _, _))
+        ));
+
+        // text settings are checked when the read options are built
+        let config = with_env("LOGD_DEDUP", "fuzzy").unwrap().validate().unwrap();
+        let error = ReadOptions::from_config(&config).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid config value dedup (LOGD_DEDUP): unknown dedup mode: fuzzy"
+        );
+        let config = with_env("LOGD_EXCLUDE", "node=a")
+            .unwrap()
+            .validate()
+            .unwrap();
+        assert!(matches!(
+            ReadOptions::from_config(&config),
+            Err(ConfigError::Invalid("exclude", _, _))
+        ));
+    }
+}
@@ -1,2 +1,60 @@
+use std::time::Duration;
+
 pub const LOG_PATH: &str = "/var/log/pods";
 pub const HIK8S_ROUTE_LOG: &str = "logs";
+pub const CHECKPOINT_PATH: &str = "/var/lib/hik8s/logd/checkpoints.json";
+pub const ENV_CHECKPOINT_PATH: &str = "LOGD_CHECKPOINT_PATH";
+pub const CHECKPOINT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+pub const BATCH_SIZE: usize = 1048576;
+pub const RECV_TIMEOUT: Duration = Duration::from_millis(500);
+pub const INOTIFY_SLEEP: Duration = Duration::from_millis(100);
+pub const ENV_CONFIG: &str = "LOGD_CONFIG";
+pub const ENV_ROUTE: &str = "LOGD_ROUTE";
+pub const ENV_BATCH_SIZE: &str = "LOGD_BATCH_SIZE";
+pub const ENV_RECV_TIMEOUT_MS: &str = "LOGD_RECV_TIMEOUT_MS";
+pub const ENV_INOTIFY_SLEEP_MS: &str = "LOGD_INOTIFY_SLEEP_MS";
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+pub const MAX_INFLIGHT_BYTES: usize = 64 * 1024 * 1024;
+pub const ENV_MAX_INFLIGHT_BYTES: &str = "LOGD_MAX_INFLIGHT_BYTES";
+pub const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+pub const ENV_WATCH_MODE: &str = "LOGD_WATCH_MODE";
+pub const ENV_POLL_INTERVAL_MS: &str = "LOGD_POLL_INTERVAL_MS";
+pub const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(20);
+pub const ENV_SHUTDOWN_GRACE_PERIOD_SECS: &str = "LOGD_SHUTDOWN_GRACE_PERIOD_SECS";
+pub const HEALTH_PORT: u16 = 8080;
+pub const ENV_HEALTH_PORT: &str = "LOGD_HEALTH_PORT";
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(120);
+pub const UPLOAD_RETRY_BACKOFF_MIN: Duration = Duration::from_secs(1);
+pub const UPLOAD_RETRY_BACKOFF_MAX: Duration = Duration::from_secs(60);
+pub const ENV_SHIP_COMPRESSED_LOGS: &str = "LOGD_SHIP_COMPRESSED_LOGS";
+pub const ENV_OUTPUT_FORMAT: &str = "LOGD_OUTPUT_FORMAT";
+pub const ENV_LOG_PATH: &str = "LOGD_LOG_PATH";
+pub const MULTILINE_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+pub const MULTILINE_MAX_LINES: usize = 500;
+pub const ENV_MULTILINE: &str = "LOGD_MULTILINE";
+pub const ENV_MULTILINE_START_PATTERN: &str = "LOGD_MULTILINE_START_PATTERN";
+pub const ENV_MULTILINE_FLUSH_TIMEOUT_MS: &str = "LOGD_MULTILINE_FLUSH_TIMEOUT_MS";
+pub const ENV_INCLUDE: &str = "LOGD_INCLUDE";
+pub const ENV_EXCLUDE: &str = "LOGD_EXCLUDE";
+pub const ENV_POD_ENRICHMENT: &str = "LOGD_POD_ENRICHMENT";
+pub const ENV_NODE_NAME: &str = "NODE_NAME";
+pub const ENV_REDACT: &str = "LOGD_REDACT";
+pub const ENV_REDACT_RULES: &str = "LOGD_REDACT_RULES";
+pub const ENV_COMPRESSION: &str = "LOGD_COMPRESSION";
+pub const ENV_POD_ANNOTATIONS: &str = "LOGD_POD_ANNOTATIONS";
+pub const ENV_SPOOL_PATH: &str = "LOGD_SPOOL_PATH";
+pub const ENV_SPOOL_MAX_BYTES: &str = "LOGD_SPOOL_MAX_BYTES";
+pub const ENV_SPOOL_MAX_AGE_SECS: &str = "LOGD_SPOOL_MAX_AGE_SECS";
+pub const SPOOL_MAX_BYTES: u64 = 256 * 1024 * 1024;
+pub const SPOOL_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+pub const ENV_RATE_LIMIT_BYTES: &str = "LOGD_RATE_LIMIT_BYTES";
+pub const ENV_RATE_LIMIT_LINES: &str = "LOGD_RATE_LIMIT_LINES";
+pub const ENV_CONTAINER_RATE_LIMIT_BYTES: &str = "LOGD_CONTAINER_RATE_LIMIT_BYTES";
+pub const ENV_CONTAINER_RATE_LIMIT_LINES: &str = "LOGD_CONTAINER_RATE_LIMIT_LINES";
+pub const DEDUP_WINDOW: Duration = Duration::from_secs(10);
+pub const ENV_DEDUP: &str = "LOGD_DEDUP";
+pub const ENV_DEDUP_WINDOW_MS: &str = "LOGD_DEDUP_WINDOW_MS";
+pub const ANNOTATION_LOGS: &str = "hik8s.ai/logs";
+pub const ANNOTATION_SAMPLE_RATE: &str = "hik8s.ai/sample-rate";
+pub const ANNOTATION_MULTILINE: &str = "hik8s.ai/multiline";
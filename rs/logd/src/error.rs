@@ -1,6 +1,9 @@
 use shared::{client::Hik8sClientError, tracing::TracingSetupError};
 use thiserror::Error;
 
+use crate::checkpoint::CheckpointError;
+use crate::config::ConfigError;
+use crate::pods::PodWatchError;
 use crate::threads::{process_file_events::EventThreadError, read_and_send::ReadThreadError};
 
 #[derive(Error, Debug)]
@@ -17,4 +20,10 @@ pub enum LogDaemonError {
     Hik8sClient(#[from] Hik8sClientError),
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("Checkpoint error: {0}")]
+    Checkpoint(#[from] CheckpointError),
+    #[error("Config error: {0}")]
+    Config(#[from] ConfigError),
+    #[error("Pod watch error: {0}")]
+    PodWatch(#[from] PodWatchError),
 }
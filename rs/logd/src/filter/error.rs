@@ -0,0 +1,9 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FilterError {
+    #[error("Invalid regex: {0}")]
+    Regex(#[from] regex::Error),
+    #[error("Invalid filter rule: {0}")]
+    Rule(String),
+}
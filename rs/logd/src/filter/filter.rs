@@ -0,0 +1,238 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::FilterError;
+
+// Splits a kubelet pod directory name: <namespace>_<pod>_<uid>
+pub fn parse_pod_dir(name: &str) -> Option<(&str, &str, &str)> {
+    // namespaces, pod names and uids contain no underscores
+    let parts: Vec<&str> = name.split('_').collect();
+    match parts[..] {
+        [namespace, pod, uid] if !namespace.is_empty() && !pod.is_empty() && !uid.is_empty() => {
+            Some((namespace, pod, uid))
+        }
+        _ => None,
+    }
+}
+
+pub fn is_docker_container_id(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// Labels the kubelet sets on the docker containers of a pod
+const DOCKER_NAMESPACE_LABEL: &str = "io.kubernetes.pod.namespace";
+const DOCKER_POD_LABEL: &str = "io.kubernetes.pod.name";
+const DOCKER_CONTAINER_LABEL: &str = "io.kubernetes.container.name";
+
+#[derive(Deserialize)]
+struct DockerConfig {
+    #[serde(rename = "Config")]
+    config: DockerContainerConfig,
+}
+
+#[derive(Deserialize)]
+struct DockerContainerConfig {
+    #[serde(rename = "Labels", default)]
+    labels: BTreeMap<String, String>,
+}
+
+// Namespace, pod and container of a docker container, from <id>/config.v2.json
+#[derive(Clone, Debug)]
+struct DockerPod {
+    namespace: String,
+    pod: String,
+    container: String,
+}
+
+impl DockerPod {
+    fn read(container_dir: &Path) -> Option<Self> {
+        let config = std::fs::read(container_dir.join("config.v2.json")).ok()?;
+        let mut labels = serde_json::from_slice::<DockerConfig>(&config)
+            .ok()?
+            .config
+            .labels;
+        Some(Self {
+            namespace: labels.remove(DOCKER_NAMESPACE_LABEL)?,
+            pod: labels.remove(DOCKER_POD_LABEL)?,
+            container: labels.remove(DOCKER_CONTAINER_LABEL)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Namespace,
+    Pod,
+    Container,
+}
+
+#[derive(Clone, Debug)]
+struct Condition {
+    field: Field,
+    pattern: Regex,
+}
+
+// Conditions joined by '&', e.g. namespace=payments&container=istio-proxy
+#[derive(Clone, Debug)]
+struct Rule {
+    conditions: Vec<Condition>,
+}
+
+impl Rule {
+    fn parse(rule: &str) -> Result<Self, FilterError> {
+        let conditions = rule
+            .split('&')
+            .map(|condition| {
+                let (field, pattern) = condition
+                    .split_once('=')
+                    .ok_or_else(|| FilterError::Rule(rule.to_string()))?;
+                let field = match field.trim() {
+                    "namespace" => Field::Namespace,
+                    "pod" => Field::Pod,
+                    "container" => Field::Container,
+                    _ => return Err(FilterError::Rule(rule.to_string())),
+                };
+                Ok(Condition {
+                    field,
+                    pattern: parse_pattern(pattern.trim())?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { conditions })
+    }
+
+    // None if the rule depends on a field that is not known yet
+    fn matches(&self, namespace: &str, pod: &str, container: Option<&str>) -> Option<bool> {
+        let mut matches = true;
+        for condition in &self.conditions {
+            let value = match condition.field {
+                Field::Namespace => namespace,
+                Field::Pod => pod,
+                Field::Container => container?,
+            };
+            matches &= condition.pattern.is_match(value);
+        }
+        Some(matches)
+    }
+}
+
+// Patterns are globs (*, ?) unless prefixed with re:
+fn parse_pattern(pattern: &str) -> Result<Regex, FilterError> {
+    if let Some(regex) = pattern.strip_prefix("re:") {
+        return Ok(Regex::new(regex)?);
+    }
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Ok(Regex::new(&regex)?)
+}
+
+fn parse_rules(rules: &str) -> Result<Vec<Rule>, FilterError> {
+    rules
+        .split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(Rule::parse)
+        .collect()
+}
+
+// Decides which containers are watched and shipped. A container is shipped if it matches
+// any include rule (or there are none) and no exclude rule.
+#[derive(Clone, Debug, Default)]
+pub struct LogFilter {
+    include: Vec<Rule>,
+    exclude: Vec<Rule>,
+    // docker containers by directory, their labels do not change
+    docker_pods: Arc<Mutex<HashMap<PathBuf, DockerPod>>>,
+}
+
+impl LogFilter {
+    pub fn new(include: &str, exclude: &str) -> Result<Self, FilterError> {
+        Ok(Self {
+            include: parse_rules(include)?,
+            exclude: parse_rules(exclude)?,
+            ..Default::default()
+        })
+    }
+
+    // container is None for a pod directory, whose containers may still be shipped
+    pub fn allows(&self, namespace: &str, pod: &str, container: Option<&str>) -> bool {
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|rule| rule.matches(namespace, pod, container) != Some(false));
+        let excluded = self
+            .exclude
+            .iter()
+            .any(|rule| rule.matches(namespace, pod, container) == Some(true));
+        included && !excluded
+    }
+
+    // Applies the rules to a pod directory, a container directory or a log file in the
+    // kubelet layout, or to a container directory or a log file in the docker layout.
+    // Paths in other layouts are always allowed.
+    pub fn allows_path(&self, path: &Path) -> bool {
+        if self.include.is_empty() && self.exclude.is_empty() {
+            return true;
+        }
+        let names: Vec<&str> = path
+            .components()
+            .rev()
+            .take(3)
+            .filter_map(|component| component.as_os_str().to_str())
+            .collect();
+        // the pod directory is the path, its parent or the parent of the container directory
+        for (depth, name) in names.iter().enumerate() {
+            if let Some((namespace, pod, _)) = parse_pod_dir(name) {
+                let container = depth.checked_sub(1).map(|index| names[index]);
+                return self.allows(namespace, pod, container);
+            }
+        }
+        let container_dir = [Some(path), path.parent()]
+            .into_iter()
+            .flatten()
+            .find(|dir| {
+                dir.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(is_docker_container_id)
+            });
+        match container_dir {
+            // its config may not be written yet, the log file is checked again
+            Some(container_dir) if container_dir == path => {
+                self.allows_docker_container(container_dir) != Some(false)
+            }
+            // a container without pod labels is not part of a pod
+            Some(container_dir) => self
+                .allows_docker_container(container_dir)
+                .unwrap_or(self.include.is_empty()),
+            None => true,
+        }
+    }
+
+    // None if the container has no config with pod labels
+    fn allows_docker_container(&self, container_dir: &Path) -> Option<bool> {
+        let mut docker_pods = self
+            .docker_pods
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !docker_pods.contains_key(container_dir) {
+            docker_pods.insert(container_dir.to_path_buf(), DockerPod::read(container_dir)?);
+        }
+        let docker_pod = &docker_pods[container_dir];
+        Some(self.allows(
+            &docker_pod.namespace,
+            &docker_pod.pod,
+            Some(&docker_pod.container),
+        ))
+    }
+}
@@ -0,0 +1,6 @@
+mod error;
+mod filter;
+mod test;
+
+pub use error::FilterError;
+pub use filter::{is_docker_container_id, parse_pod_dir, LogFilter};
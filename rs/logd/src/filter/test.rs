@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use tempfile::tempdir;
+
+    use super::super::{parse_pod_dir, LogFilter};
+
+    #[test]
+    fn test_parse_pod_dir() {
+        assert_eq!(
+            parse_pod_dir("kube-system_coredns-5d78c9869d-x2vbn_1f5c6f3e"),
+            Some(("kube-system", "coredns-5d78c9869d-x2vbn", "1f5c6f3e"))
+        );
+        assert!(parse_pod_dir("pods").is_none());
+        assert!(parse_pod_dir("default_web").is_none());
+        assert!(parse_pod_dir("default__uid").is_none());
+    }
+
+    #[test]
+    fn test_filter_globs_and_regexes() {
+        let filter = LogFilter::new(
+            "",
+            "namespace=kube-*, container=istio-proxy, pod=re:^batch-\\d+$",
+        )
+        .unwrap();
+        assert!(!filter.allows("kube-system", "coredns", Some("coredns")));
+        assert!(!filter.allows("default", "web", Some("istio-proxy")));
+        assert!(!filter.allows("default", "batch-42", Some("job")));
+        assert!(filter.allows("default", "batch-x", Some("job")));
+        assert!(filter.allows("default", "web", Some("web")));
+        // the container of a pod directory is unknown
+        assert!(filter.allows("default", "web", None));
+    }
+
+    #[test]
+    fn test_filter_include_and_combined_conditions() {
+        let filter = LogFilter::new(
+            "namespace=payments, namespace=shop",
+            "namespace=payments&container=istio-proxy",
+        )
+        .unwrap();
+        assert!(filter.allows("payments", "api", Some("api")));
+        assert!(!filter.allows("payments", "api", Some("istio-proxy")));
+        assert!(filter.allows("shop", "web", Some("istio-proxy")));
+        assert!(!filter.allows("default", "web", Some("web")));
+        assert!(!filter.allows("default", "web", None));
+
+        // a pod directory is watched if any of its containers may be shipped
+        let filter = LogFilter::new("container=api", "").unwrap();
+        assert!(filter.allows("default", "web", None));
+        assert!(!filter.allows("default", "web", Some("web")));
+
+        assert!(LogFilter::new("image=nginx", "").is_err());
+        assert!(LogFilter::new("", "pod").is_err());
+        assert!(LogFilter::new("", "pod=re:(").is_err());
+    }
+
+    #[test]
+    fn test_filter_allows_path() {
+        let filter = LogFilter::new("", "namespace=kube-system, container=istio-proxy").unwrap();
+        let pod_dir = Path::new("/var/log/pods/kube-system_coredns_1f5c6f3e");
+        assert!(!filter.allows_path(pod_dir));
+        assert!(!filter.allows_path(&pod_dir.join("coredns")));
+        assert!(!filter.allows_path(&pod_dir.join("coredns/0.log")));
+
+        let pod_dir = Path::new("/var/log/pods/default_web_4a8e9c55");
+        assert!(filter.allows_path(pod_dir));
+        assert!(filter.allows_path(&pod_dir.join("web/0.log.20240101-000000.gz")));
+        assert!(!filter.allows_path(&pod_dir.join("istio-proxy")));
+        assert!(!filter.allows_path(&pod_dir.join("istio-proxy/0.log")));
+
+        // other layouts are not filtered
+        assert!(filter.allows_path(Path::new("/var/log/pods")));
+        assert!(filter.allows_path(Path::new("/var/lib/docker/containers/abc/abc-json.log")));
+    }
+
+    fn docker_container(root: &Path, id: char, namespace: &str, container: &str) -> PathBuf {
+        let id = id.to_string().repeat(64);
+        let container_dir = root.join(&id);
+        std::fs::create_dir_all(&container_dir).unwrap();
+        let config = serde_json::json!({
+            "Config": {
+                "Labels": {
+                    "io.kubernetes.pod.namespace": namespace,
+                    "io.kubernetes.pod.name": "web",
+                    "io.kubernetes.container.name": container
+                }
+            }
+        });
+        std::fs::write(container_dir.join("config.v2.json"), config.to_string()).unwrap();
+        container_dir.join(format!("{id}-json.log"))
+    }
+
+    #[test]
+    fn test_filter_allows_docker_path() {
+        let temp_dir = tempdir().unwrap();
+        let filter = LogFilter::new("", "namespace=kube-system, container=istio-proxy").unwrap();
+        let coredns = docker_container(temp_dir.path(), 'a', "kube-system", "coredns");
+        assert!(!filter.allows_path(&coredns));
+        assert!(!filter.allows_path(coredns.parent().unwrap()));
+        assert!(!filter.allows_path(&docker_container(
+            temp_dir.path(),
+            'b',
+            "default",
+            "istio-proxy"
+        )));
+        let web = docker_container(temp_dir.path(), 'c', "default", "web");
+        assert!(filter.allows_path(&web));
+        assert!(filter.allows_path(&web.with_extension("log.1")));
+
+        // a container without pod labels is only shipped without include rules, its directory
+        // is watched until the config is written
+        let plain_dir = temp_dir.path().join("d".repeat(64));
+        std::fs::create_dir_all(&plain_dir).unwrap();
+        let plain_log = plain_dir.join(format!("{}-json.log", "d".repeat(64)));
+        assert!(filter.allows_path(&plain_log));
+        let filter = LogFilter::new("namespace=default", "").unwrap();
+        assert!(filter.allows_path(&plain_dir));
+        assert!(!filter.allows_path(&plain_log));
+        assert!(filter.allows_path(&web));
+        assert!(!filter.allows_path(&coredns));
+    }
+}
@@ -1,21 +1,38 @@
-use constant::LOG_PATH;
+#![allow(clippy::module_inception)]
+
+use checkpoint::CheckpointStore;
+use config::Config;
+use constant::{
+    CHECKPOINT_FLUSH_INTERVAL, ENV_NODE_NAME, EVENT_CHANNEL_CAPACITY, HEARTBEAT_TIMEOUT,
+};
 use error::LogDaemonError;
+use pods::{watch_pods, PodCache, PodWatchError};
 use shared::client::Hik8sClient;
+use shared::env::get_env_var;
+use shared::health::{serve, Health};
+use shutdown::{handle_signals, termination_signals};
 use threads::process_file_events::process_file_events;
-use threads::read_and_send::read_file_and_send_data;
+use threads::read_and_send::{read_file_and_send_data, ReadOptions};
 
 use shared::tracing::setup_tracing;
-use tokio::task::JoinHandle;
+use tokio::runtime::Handle;
+use tokio::task::{spawn_blocking, JoinHandle};
 use tracing::{error, info};
 
+mod checkpoint;
+mod config;
 mod constant;
 mod error;
+mod filter;
+mod metrics;
+mod pods;
+mod shutdown;
+mod spool;
 mod test;
 mod threads;
 mod util;
 
-use std::path::Path;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc};
 
 #[tokio::main]
@@ -23,35 +40,112 @@ async fn main() -> Result<(), LogDaemonError> {
     setup_tracing()?;
     info!("Starting logd...");
 
+    // Settings of the file in LOGD_CONFIG, overridden by env vars
+    let config = Config::load()?;
+    info!("Config: {config:?}");
+
     // Track threads
     let mut threads: Vec<JoinHandle<Result<(), LogDaemonError>>> = Vec::new();
 
     let termination_signal = Arc::new(AtomicBool::new(false));
+    // Set on SIGTERM and SIGINT
+    let termination_signal_clone = Arc::clone(&termination_signal);
+    tokio::spawn(async move {
+        handle_signals(termination_signals(), termination_signal_clone)
+            .await
+            .inspect_err(|e| error!("Error: Signal handler exit: {}", e))
+    });
     let termination_signal_clone = Arc::clone(&termination_signal);
 
-    // File events thread
-    let (file_event_sender, file_event_receiver) = mpsc::channel();
-    threads.push(tokio::spawn(async move {
+    // Probes and metrics, ready once the token was acquired and the read thread caught up, live
+    // while both threads beat
+    let health = Health::new();
+    let health_port = config.health_port;
+    let router = health.router().merge(shared::metrics::router());
+    tokio::spawn(async move {
+        serve(health_port, router)
+            .await
+            .inspect_err(|e| error!("Error: Health server exit: {}", e))
+    });
+
+    // Source root, /var/lib/docker/containers on nodes with the docker json-file layout
+    let log_path = config.log_path.clone();
+    info!("Reading logs from {}", log_path.display());
+
+    // Fallible setup is done before the threads start, a running thread would keep the runtime
+    // from shutting down on an error
+    let options = ReadOptions::from_config(&config)?;
+    let checkpoints = CheckpointStore::load(&config.checkpoint_path, CHECKPOINT_FLUSH_INTERVAL)?;
+
+    // Pods on this node, only watched for enrichment and annotation settings. Files are not read
+    // before the first list, an opted-out pod would be shipped while it is unknown
+    let watch = options.pod_enrichment || options.pod_annotations;
+    let pods = if watch {
+        PodCache::unlisted()
+    } else {
+        PodCache::default()
+    };
+    if watch {
+        let node_name = get_env_var(ENV_NODE_NAME).map_err(PodWatchError::Env)?;
+        let kube_client = kube::Client::try_default()
+            .await
+            .map_err(PodWatchError::Kube)?;
+        let pods = pods.clone();
+        tokio::spawn(async move {
+            watch_pods(kube_client, &node_name, pods)
+                .await
+                .inspect_err(|e| error!("Error: Pod watcher exit: {}", e))
+        });
+    }
+
+    let client = Hik8sClient::new(false)?.with_health(health.clone());
+    let client_clone = client.clone();
+    tokio::spawn(async move { client_clone.authenticate().await });
+
+    // File events thread, blocks in inotify and sleeps, so it runs on the blocking pool to keep
+    // the workers free for the signal handler and the probes
+    let (file_event_sender, file_event_receiver) = mpsc::sync_channel(EVENT_CHANNEL_CAPACITY);
+    let filter = options.filter.clone();
+    let watch = options.watch;
+    let checkpoints_clone = checkpoints.clone();
+    let heartbeat = health.heartbeat("process_file_events", Some(HEARTBEAT_TIMEOUT));
+    threads.push(spawn_blocking(move || {
         process_file_events(
-            Path::new(LOG_PATH),
+            &log_path,
             file_event_sender,
-            termination_signal_clone,
+            filter,
+            checkpoints_clone,
+            watch,
+            heartbeat,
+            termination_signal_clone.clone(),
         )
         .map_err(|e| {
             error!("Error: Thread exit in process_file_events: {}", e);
+            termination_signal_clone.store(true, Ordering::SeqCst);
             e
         })?;
         Ok(())
     }));
 
-    // Read and send thread
-    let client = Hik8sClient::new(false)?;
+    // Read and send thread, blocks on the file events channel between uploads
+    let heartbeat = health.heartbeat("read_file_and_send_data", Some(HEARTBEAT_TIMEOUT));
     let termination_signal_clone = Arc::clone(&termination_signal);
-    threads.push(tokio::spawn(async move {
-        read_file_and_send_data(file_event_receiver, client, termination_signal_clone)
-            .await
+    let runtime = Handle::current();
+    threads.push(spawn_blocking(move || {
+        runtime
+            .block_on(read_file_and_send_data(
+                file_event_receiver,
+                client,
+                checkpoints,
+                options,
+                pods,
+                heartbeat,
+                termination_signal_clone.clone(),
+            ))
             .map_err(|e| {
                 error!("Error: Thread exit in read_file_and_send_data: {}", e);
+                // the file events thread would keep running
+                termination_signal_clone.store(true, Ordering::SeqCst);
                 e
             })?;
         Ok(())
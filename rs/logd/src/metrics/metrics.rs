@@ -0,0 +1,119 @@
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
+use std::sync::LazyLock;
+
+// Metrics are registered in the default prometheus registry
+
+pub static REDACTIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "logd_redactions_total",
+        "Secrets and personal data redacted from log lines, by rule",
+        &["rule"]
+    )
+    .unwrap()
+});
+
+pub static SPOOL_SEGMENTS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "logd_spool_segments",
+        "Chunks in the spool waiting to be uploaded"
+    )
+    .unwrap()
+});
+
+pub static SPOOL_BYTES: LazyLock<IntGauge> =
+    LazyLock::new(|| register_int_gauge!("logd_spool_bytes", "Size of the spool on disk").unwrap());
+
+pub static SPOOL_EVICTIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "logd_spool_evictions_total",
+        "Spooled chunks dropped before they were uploaded, by the exceeded limit",
+        &["reason"]
+    )
+    .unwrap()
+});
+
+pub static RATE_LIMITED_LINES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "logd_rate_limited_lines_total",
+        "Lines dropped by the rate limit, by the exceeded node or container limit",
+        &["scope"]
+    )
+    .unwrap()
+});
+
+pub static FILES_WATCHED: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "logd_files_watched",
+        "Log files watched with inotify or polling"
+    )
+    .unwrap()
+});
+
+pub static BYTES_READ: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("logd_read_bytes_total", "Bytes read from log files").unwrap()
+});
+
+pub static BYTES_UPLOADED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "logd_uploaded_bytes_total",
+        "Bytes of uploaded chunks before compression"
+    )
+    .unwrap()
+});
+
+pub static UPLOAD_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "logd_upload_duration_seconds",
+        "Duration of uploads, by success or failure",
+        &["result"],
+        vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]
+    )
+    .unwrap()
+});
+
+pub static UPLOAD_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "logd_upload_failures_total",
+        "Failed uploads, by the status code of the response or error without a response",
+        &["status"]
+    )
+    .unwrap()
+});
+
+pub static REJECTED_CHUNKS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "logd_rejected_chunks_total",
+        "Chunks dropped because the server rejected them for good, by status code",
+        &["status"]
+    )
+    .unwrap()
+});
+
+pub static INOTIFY_OVERFLOWS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "logd_inotify_overflows_total",
+        "Overflows of the inotify event queue, each is followed by a rescan"
+    )
+    .unwrap()
+});
+
+pub static FILE_LAG: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "logd_file_lag_bytes",
+        "Size of a log file minus its committed offset, by path",
+        &["path"]
+    )
+    .unwrap()
+});
+
+pub static CHANNEL_DEPTH: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "logd_channel_depth",
+        "Messages sent to a channel and not yet received, by channel",
+        &["channel"]
+    )
+    .unwrap()
+});
@@ -0,0 +1,7 @@
+mod metrics;
+
+pub use metrics::{
+    BYTES_READ, BYTES_UPLOADED, CHANNEL_DEPTH, FILES_WATCHED, FILE_LAG, INOTIFY_OVERFLOWS,
+    RATE_LIMITED_LINES, REDACTIONS, REJECTED_CHUNKS, SPOOL_BYTES, SPOOL_EVICTIONS, SPOOL_SEGMENTS,
+    UPLOAD_DURATION, UPLOAD_FAILURES,
+};
@@ -0,0 +1,10 @@
+use shared::env::EnvError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PodWatchError {
+    #[error("Kubernetes client error: {0}")]
+    Kube(#[from] kube::Error),
+    #[error("Env error: {0}")]
+    Env(#[from] EnvError),
+}
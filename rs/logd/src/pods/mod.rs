@@ -0,0 +1,10 @@
+mod error;
+mod pods;
+mod settings;
+mod test;
+mod watcher;
+
+pub use error::PodWatchError;
+pub use pods::{PodCache, PodInfo};
+pub use settings::PodSettings;
+pub use watcher::watch_pods;
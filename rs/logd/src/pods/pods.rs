@@ -0,0 +1,134 @@
+use k8s_openapi::api::core::v1::Pod;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use super::PodSettings;
+
+// Large annotations that add nothing to a log record
+const SKIPPED_ANNOTATIONS: [&str; 1] = ["kubectl.kubernetes.io/last-applied-configuration"];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PodOwner {
+    pub kind: String,
+    pub name: String,
+    pub uid: String,
+}
+
+// Pod metadata attached to the uploads of its containers
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PodInfo {
+    pub labels: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
+    pub owner_references: Vec<PodOwner>,
+    // image of each container, including init containers
+    pub images: BTreeMap<String, String>,
+    // read from the annotations, not uploaded
+    #[serde(skip)]
+    pub settings: PodSettings,
+}
+
+impl From<&Pod> for PodInfo {
+    fn from(pod: &Pod) -> Self {
+        let metadata = &pod.metadata;
+        let annotations: BTreeMap<String, String> = metadata
+            .annotations
+            .iter()
+            .flatten()
+            .filter(|(key, _)| !SKIPPED_ANNOTATIONS.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let name = metadata.name.as_deref().unwrap_or_default();
+        let settings = PodSettings::from_annotations(name, &annotations);
+        let owner_references = metadata
+            .owner_references
+            .iter()
+            .flatten()
+            .map(|owner| PodOwner {
+                kind: owner.kind.clone(),
+                name: owner.name.clone(),
+                uid: owner.uid.clone(),
+            })
+            .collect();
+        let images = pod
+            .spec
+            .iter()
+            .flat_map(|spec| {
+                spec.init_containers
+                    .iter()
+                    .flatten()
+                    .chain(&spec.containers)
+            })
+            .filter_map(|container| Some((container.name.clone(), container.image.clone()?)))
+            .collect();
+
+        Self {
+            labels: metadata.labels.clone().unwrap_or_default(),
+            annotations,
+            owner_references,
+            images,
+            settings,
+        }
+    }
+}
+
+// Pods scheduled on the local node, keyed by pod UID
+#[derive(Clone, Default)]
+pub struct PodCache {
+    pods: Arc<RwLock<HashMap<String, PodInfo>>>,
+    // set until the watcher has listed the pods for the first time
+    unlisted: Arc<AtomicBool>,
+}
+
+impl PodCache {
+    // A cache filled by the watcher, empty until its first list is done
+    pub fn unlisted() -> Self {
+        Self {
+            unlisted: Arc::new(AtomicBool::new(true)),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_listed(&self) -> bool {
+        !self.unlisted.load(Ordering::SeqCst)
+    }
+
+    pub fn get(&self, uid: &str) -> Option<PodInfo> {
+        self.pods
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(uid)
+            .cloned()
+    }
+
+    pub fn apply(&self, pod: &Pod) {
+        let Some(uid) = &pod.metadata.uid else {
+            return;
+        };
+        self.pods
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(uid.clone(), PodInfo::from(pod));
+    }
+
+    pub fn delete(&self, pod: &Pod) {
+        let Some(uid) = &pod.metadata.uid else {
+            return;
+        };
+        self.pods
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(uid);
+    }
+
+    // Replaces all pods after the watch was (re)started
+    pub fn replace(&self, pods: &[Pod]) {
+        let pods = pods
+            .iter()
+            .filter_map(|pod| Some((pod.metadata.uid.clone()?, PodInfo::from(pod))))
+            .collect();
+        *self.pods.write().unwrap_or_else(PoisonError::into_inner) = pods;
+        self.unlisted.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+use tracing::warn;
+
+use crate::constant::{ANNOTATION_LOGS, ANNOTATION_MULTILINE, ANNOTATION_SAMPLE_RATE};
+use crate::threads::read_and_send::MultilinePattern;
+
+// Log settings of a pod, chosen by its owners through annotations
+#[derive(Debug, Clone, Default)]
+pub struct PodSettings {
+    // hik8s.ai/logs: disabled
+    pub logs_disabled: bool,
+    // hik8s.ai/sample-rate: fraction of records to ship, 0.0 to 1.0
+    pub sample_rate: Option<f64>,
+    // hik8s.ai/multiline: presets that replace LOGD_MULTILINE
+    pub multiline: Option<MultilinePattern>,
+}
+
+impl PodSettings {
+    // Invalid values are ignored with a warning
+    pub fn from_annotations(pod: &str, annotations: &BTreeMap<String, String>) -> Self {
+        let logs_disabled = annotations
+            .get(ANNOTATION_LOGS)
+            .is_some_and(|value| value.trim() == "disabled");
+
+        let sample_rate = annotations.get(ANNOTATION_SAMPLE_RATE).and_then(|value| {
+            match value.trim().parse::<f64>() {
+                Ok(rate) if (0.0..=1.0).contains(&rate) => Some(rate),
+                _ => {
+                    warn!(
+                        "Pod {pod}: ignoring {ANNOTATION_SAMPLE_RATE}={value}, expected 0.0 to 1.0"
+                    );
+                    None
+                }
+            }
+        });
+
+        let multiline = annotations.get(ANNOTATION_MULTILINE).and_then(|value| {
+            MultilinePattern::from_presets(value)
+                .inspect_err(|e| warn!("Pod {pod}: ignoring {ANNOTATION_MULTILINE}: {e}"))
+                .ok()
+        });
+
+        Self {
+            logs_disabled,
+            sample_rate,
+            multiline,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::core::v1::Pod;
+    use std::collections::BTreeMap;
+
+    use super::super::{PodCache, PodInfo, PodSettings};
+
+    fn pod(uid: &str, app: &str) -> Pod {
+        serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": format!("{app}-7d9f8b6c5d-x2vbn"),
+                "namespace": "default",
+                "uid": uid,
+                "labels": {"app": app},
+                "annotations": {
+                    "prometheus.io/scrape": "true",
+                    "kubectl.kubernetes.io/last-applied-configuration": "{}"
+                },
+                "ownerReferences": [{
+                    "apiVersion": "apps/v1",
+                    "kind": "ReplicaSet",
+                    "name": format!("{app}-7d9f8b6c5d"),
+                    "uid": "6c1f0f6e-3b0a-4a8e-9c55-1d2e3f4a5b6c"
+                }]
+            },
+            "spec": {
+                "initContainers": [{"name": "migrate", "image": "migrate:1.0"}],
+                "containers": [{"name": app, "image": format!("{app}:2.1")}]
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pod_info_from_pod() {
+        let info = PodInfo::from(&pod("uid-1", "web"));
+        assert_eq!(info.labels.get("app").unwrap(), "web");
+        assert_eq!(info.annotations.len(), 1);
+        assert_eq!(
+            info.annotations.get("prometheus.io/scrape").unwrap(),
+            "true"
+        );
+        assert_eq!(info.owner_references.len(), 1);
+        assert_eq!(info.owner_references[0].kind, "ReplicaSet");
+        assert_eq!(info.owner_references[0].name, "web-7d9f8b6c5d");
+        assert_eq!(info.images.get("migrate").unwrap(), "migrate:1.0");
+        assert_eq!(info.images.get("web").unwrap(), "web:2.1");
+    }
+
+    #[test]
+    fn test_pod_cache() {
+        let cache = PodCache::default();
+        cache.apply(&pod("uid-1", "web"));
+        cache.apply(&pod("uid-2", "api"));
+        assert_eq!(
+            cache.get("uid-1").unwrap().labels.get("app").unwrap(),
+            "web"
+        );
+
+        cache.delete(&pod("uid-1", "web"));
+        assert!(cache.get("uid-1").is_none());
+
+        // A relist drops pods that are gone
+        cache.replace(&[pod("uid-3", "db")]);
+        assert!(cache.is_listed());
+        assert!(cache.get("uid-2").is_none());
+        assert!(cache.get("uid-3").is_some());
+    }
+
+    #[test]
+    fn test_pod_cache_listed() {
+        let cache = PodCache::unlisted();
+        assert!(!cache.is_listed());
+        cache.apply(&pod("uid-1", "web"));
+        assert!(!cache.is_listed());
+        cache.replace(&[]);
+        assert!(cache.is_listed());
+        assert!(PodCache::default().is_listed());
+    }
+
+    #[test]
+    fn test_pod_settings_from_annotations() {
+        let annotations = BTreeMap::from([
+            ("hik8s.ai/logs".to_string(), "disabled".to_string()),
+            ("hik8s.ai/sample-rate".to_string(), "0.25".to_string()),
+            ("hik8s.ai/multiline".to_string(), "java,go".to_string()),
+        ]);
+        let settings = PodSettings::from_annotations("web", &annotations);
+        assert!(settings.logs_disabled);
+        assert_eq!(settings.sample_rate, Some(0.25));
+        assert!(settings
+            .multiline
+            .unwrap()
+            .is_continuation("\tat com.example.Main.main(Main.java:5)"));
+
+        // Invalid values are ignored
+        let annotations = BTreeMap::from([
+            ("hik8s.ai/logs".to_string(), "enabled".to_string()),
+            ("hik8s.ai/sample-rate".to_string(), "2".to_string()),
+            ("hik8s.ai/multiline".to_string(), "cobol".to_string()),
+        ]);
+        let settings = PodSettings::from_annotations("web", &annotations);
+        assert!(!settings.logs_disabled);
+        assert!(settings.sample_rate.is_none());
+        assert!(settings.multiline.is_none());
+    }
+}
@@ -0,0 +1,38 @@
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::Pod;
+use kube::runtime::watcher::{watcher, Config, Event};
+use kube::runtime::WatchStreamExt;
+use kube::Api;
+use tracing::{debug, info, warn};
+
+use super::{PodCache, PodWatchError};
+
+// Keeps the cache in sync with the pods scheduled on node_name
+pub async fn watch_pods(
+    client: kube::Client,
+    node_name: &str,
+    cache: PodCache,
+) -> Result<(), PodWatchError> {
+    info!("Watching pods on node {node_name}");
+    let api: Api<Pod> = Api::all(client);
+    let config = Config::default().fields(&format!("spec.nodeName={node_name}"));
+    let mut events = watcher(api, config).default_backoff().boxed();
+
+    // pods of a (re)list, applied at once so deleted pods do not stay in the cache
+    let mut listed = Vec::new();
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(Event::Init) => listed.clear(),
+            Ok(Event::InitApply(pod)) => listed.push(pod),
+            Ok(Event::InitDone) => {
+                debug!("Listed {} pods", listed.len());
+                cache.replace(&listed);
+                listed.clear();
+            }
+            Ok(Event::Apply(pod)) => cache.apply(&pod),
+            Ok(Event::Delete(pod)) => cache.delete(&pod),
+            Err(e) => warn!("Pod watcher error: {e}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,3 @@
+mod shutdown;
+
+pub use shutdown::{handle_signals, termination_signals};
@@ -0,0 +1,29 @@
+use std::future::Future;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::info;
+
+// Resolves to the name of the first SIGTERM or SIGINT
+pub async fn termination_signals() -> Result<&'static str, io::Error> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let name = tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = interrupt.recv() => "SIGINT",
+    };
+    Ok(name)
+}
+
+// Sets the termination signal once `signals` resolves. The file events thread stops and the
+// read thread drains the pending files within the grace period, then persists offsets.
+pub async fn handle_signals(
+    signals: impl Future<Output = Result<&'static str, io::Error>>,
+    termination_signal: Arc<AtomicBool>,
+) -> Result<(), io::Error> {
+    let name = signals.await?;
+    info!("Received {name}, shutting down...");
+    termination_signal.store(true, Ordering::SeqCst);
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SpoolError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Json serialize error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Corrupt spool segment: {0}")]
+    Corrupt(PathBuf),
+}
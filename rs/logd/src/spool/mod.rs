@@ -0,0 +1,6 @@
+mod error;
+mod spool;
+mod test;
+
+pub use error::SpoolError;
+pub use spool::{Spool, SpoolOptions};
@@ -0,0 +1,190 @@
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+use crate::metrics::{SPOOL_BYTES, SPOOL_EVICTIONS, SPOOL_SEGMENTS};
+
+use super::SpoolError;
+
+#[derive(Clone, Debug)]
+pub struct SpoolOptions {
+    pub path: PathBuf,
+    pub max_bytes: u64,
+    pub max_age: Duration,
+}
+
+struct Segment {
+    path: PathBuf,
+    size: u64,
+    created: SystemTime,
+}
+
+// Chunks that could not be uploaded, one segment file per chunk. A segment holds the upload
+// metadata as a JSON line followed by the encoded data. Segments are named by a sequence
+// number and replayed in that order.
+pub struct Spool {
+    options: SpoolOptions,
+    segments: VecDeque<Segment>,
+    bytes: u64,
+    next_sequence: u64,
+}
+
+impl Spool {
+    pub fn open(options: SpoolOptions) -> Result<Self, SpoolError> {
+        fs::create_dir_all(&options.path)?;
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&options.path)? {
+            let path = entry?.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("seg") => {
+                    let Some(sequence) = path
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .and_then(|stem| stem.parse::<u64>().ok())
+                    else {
+                        continue;
+                    };
+                    let metadata = fs::metadata(&path)?;
+                    let segment = Segment {
+                        path,
+                        size: metadata.len(),
+                        created: metadata.modified()?,
+                    };
+                    segments.push((sequence, segment));
+                }
+                // interrupted write
+                Some("tmp") => fs::remove_file(&path)?,
+                _ => {}
+            }
+        }
+        segments.sort_by_key(|(sequence, _)| *sequence);
+        let next_sequence = segments.last().map_or(0, |(sequence, _)| sequence + 1);
+        let segments: VecDeque<Segment> = segments.into_iter().map(|(_, s)| s).collect();
+        let bytes = segments.iter().map(|segment| segment.size).sum();
+        info!(
+            "Loaded {} spooled segments ({} bytes) from {}",
+            segments.len(),
+            bytes,
+            options.path.display()
+        );
+
+        let mut spool = Self {
+            options,
+            segments,
+            bytes,
+            next_sequence,
+        };
+        spool.evict();
+        spool.update_metrics();
+        Ok(spool)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.options.path
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    // Appends a chunk as a new segment, evicting the oldest segments if the spool is full
+    pub fn push(&mut self, metadata: &serde_json::Value, data: &[u8]) -> Result<(), SpoolError> {
+        let path = self
+            .options
+            .path
+            .join(format!("{:020}.seg", self.next_sequence));
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(&mut writer, metadata)?;
+        writer.write_all(b"\n")?;
+        writer.write_all(data)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        self.next_sequence += 1;
+
+        let metadata = fs::metadata(&path)?;
+        self.bytes += metadata.len();
+        self.segments.push_back(Segment {
+            path,
+            size: metadata.len(),
+            created: metadata.modified()?,
+        });
+        self.evict();
+        self.update_metrics();
+        Ok(())
+    }
+
+    // Metadata and data of the oldest segment
+    pub fn front(&self) -> Option<Result<(serde_json::Value, Bytes), SpoolError>> {
+        self.segments
+            .front()
+            .map(|segment| read_segment(&segment.path))
+    }
+
+    pub fn pop_front(&mut self) -> Result<(), SpoolError> {
+        let Some(segment) = self.segments.pop_front() else {
+            return Ok(());
+        };
+        self.bytes -= segment.size;
+        self.update_metrics();
+        match fs::remove_file(&segment.path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Drops the oldest segments while they are older than max_age or the spool is larger
+    // than max_bytes
+    pub fn evict(&mut self) {
+        while let Some(segment) = self.segments.front() {
+            let age = segment.created.elapsed().unwrap_or_default();
+            let reason = if age > self.options.max_age {
+                "max_age"
+            } else if self.bytes > self.options.max_bytes {
+                "max_bytes"
+            } else {
+                break;
+            };
+            warn!(
+                "Evicting spooled segment {} ({} bytes), {} exceeded",
+                segment.path.display(),
+                segment.size,
+                reason
+            );
+            SPOOL_EVICTIONS.with_label_values(&[reason]).inc();
+            if let Err(e) = self.pop_front() {
+                warn!("Failed to remove spooled segment: {e}");
+            }
+        }
+    }
+
+    fn update_metrics(&self) {
+        SPOOL_SEGMENTS.set(self.segments.len() as i64);
+        SPOOL_BYTES.set(self.bytes as i64);
+    }
+}
+
+fn read_segment(path: &Path) -> Result<(serde_json::Value, Bytes), SpoolError> {
+    let content = fs::read(path)?;
+    let split = content
+        .iter()
+        .position(|b| *b == b'\n')
+        .ok_or_else(|| SpoolError::Corrupt(path.to_path_buf()))?;
+    let metadata = serde_json::from_slice(&content[..split])?;
+    Ok((metadata, Bytes::copy_from_slice(&content[split + 1..])))
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    use crate::spool::{Spool, SpoolError, SpoolOptions};
+
+    fn options(path: &std::path::Path, max_bytes: u64, max_age: Duration) -> SpoolOptions {
+        SpoolOptions {
+            path: path.join("spool"),
+            max_bytes,
+            max_age,
+        }
+    }
+
+    #[test]
+    fn test_spool_replays_in_order() -> Result<(), SpoolError> {
+        let temp_dir = tempdir()?;
+        let options = options(temp_dir.path(), 1 << 20, Duration::from_secs(3600));
+
+        let mut spool = Spool::open(options.clone())?;
+        assert!(spool.is_empty());
+        spool.push(&serde_json::json!({"file": "0.log"}), b"first\n")?;
+        spool.push(&serde_json::json!({"file": "1.log"}), b"second\n")?;
+
+        // Segments survive a restart
+        let mut spool = Spool::open(options)?;
+        assert_eq!(spool.len(), 2);
+        let (metadata, data) = spool.front().unwrap()?;
+        assert_eq!(metadata["file"], "0.log");
+        assert_eq!(&data[..], b"first\n");
+
+        spool.pop_front()?;
+        spool.push(&serde_json::json!({"file": "2.log"}), b"third\n")?;
+        let (metadata, data) = spool.front().unwrap()?;
+        assert_eq!(metadata["file"], "1.log");
+        assert_eq!(&data[..], b"second\n");
+        spool.pop_front()?;
+        let (metadata, _) = spool.front().unwrap()?;
+        assert_eq!(metadata["file"], "2.log");
+        spool.pop_front()?;
+        assert!(spool.is_empty());
+        assert_eq!(spool.bytes(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spool_evicts_oldest_over_max_bytes() -> Result<(), SpoolError> {
+        let temp_dir = tempdir()?;
+        let mut spool = Spool::open(options(temp_dir.path(), 100, Duration::from_secs(3600)))?;
+
+        let metadata = serde_json::json!({});
+        for i in 0..4 {
+            spool.push(&metadata, format!("{i}{}\n", "x".repeat(40)).as_bytes())?;
+        }
+        assert_eq!(spool.len(), 2);
+        assert!(spool.bytes() <= 100);
+        let (_, data) = spool.front().unwrap()?;
+        assert!(data.starts_with(b"2"));
+        assert_eq!(std::fs::read_dir(spool.path())?.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spool_evicts_over_max_age() -> Result<(), SpoolError> {
+        let temp_dir = tempdir()?;
+        let options = options(temp_dir.path(), 1 << 20, Duration::from_millis(100));
+        let mut spool = Spool::open(options.clone())?;
+        spool.push(&serde_json::json!({}), b"old\n")?;
+        assert_eq!(spool.len(), 1);
+
+        std::thread::sleep(Duration::from_millis(200));
+        spool.evict();
+        assert!(spool.is_empty());
+        Ok(())
+    }
+}
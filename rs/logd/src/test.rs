@@ -1,20 +1,27 @@
 #[cfg(test)]
 mod integration_tests {
-    use shared::client::Hik8sClient;
+    use shared::client::{Hik8sClient, MockHik8sClient};
     use std::env;
     use std::fs::create_dir;
     use std::sync::atomic::{AtomicBool, Ordering};
-    use std::sync::{mpsc, Arc};
+    use std::sync::{mpsc, Arc, Mutex};
     use std::time::{Duration, Instant};
     use tempfile::tempdir;
-    use tokio::task::JoinHandle;
+    use tokio::runtime::Handle;
+    use tokio::sync::oneshot;
+    use tokio::task::{spawn_blocking, JoinHandle};
     use tracing::debug;
 
+    use crate::checkpoint::CheckpointStore;
     use crate::constant::HIK8S_ROUTE_LOG;
     use crate::error::LogDaemonError;
-    use crate::threads::process_file_events::process_file_events;
-    use crate::threads::read_and_send::read_file_and_send_data;
-    use crate::util::test::test_util::create_test_file;
+    use crate::filter::LogFilter;
+    use crate::pods::PodCache;
+    use crate::shutdown::handle_signals;
+    use crate::threads::process_file_events::{process_file_events, WatchOptions};
+    use crate::threads::read_and_send::{read_file_and_send_data, MultilinePattern, ReadOptions};
+    use crate::util::test::test_util::{create_test_file, write_to_existing_file};
+    use shared::health::Heartbeat;
     use shared::tracing::setup_tracing;
 
     use httpmock::Method::POST;
@@ -49,24 +56,48 @@ mod integration_tests {
         let sig_term = Arc::new(AtomicBool::new(false));
         let sig_term_clone = sig_term.clone();
 
+        let checkpoint_dir = tempdir().expect("Failed to create temp dir");
+        let checkpoints = CheckpointStore::load(
+            &checkpoint_dir.path().join("checkpoints.json"),
+            Duration::from_secs(60),
+        )?;
+
+        // Mock client, created before the threads so that an error does not leave them running
+        dotenv::dotenv().ok();
+        env::set_var("HIK8S_PORT", server_port);
+        let client = Hik8sClient::new(true)?;
+
         // File events thread
         let temp_path_clone = temp_path.clone();
-        let (file_event_sender, file_event_receiver) = mpsc::channel();
-        threads.push(tokio::spawn(async move {
-            process_file_events(&temp_path_clone, file_event_sender, sig_term_clone)?;
+        let checkpoints_clone = checkpoints.clone();
+        let (file_event_sender, file_event_receiver) = mpsc::sync_channel(16);
+        threads.push(spawn_blocking(move || {
+            process_file_events(
+                &temp_path_clone,
+                file_event_sender,
+                LogFilter::default(),
+                checkpoints_clone,
+                WatchOptions::default(),
+                Heartbeat::default(),
+                sig_term_clone,
+            )?;
             debug!("File events thread finished");
             Ok(())
         }));
 
-        // Mock client
-        dotenv::dotenv().ok();
-        env::set_var("HIK8S_PORT", server_port);
-        let client = Hik8sClient::new(true)?;
-
         // Read and send thread
         let sig_term_clone = sig_term.clone();
-        threads.push(tokio::spawn(async move {
-            read_file_and_send_data(file_event_receiver, client, sig_term_clone).await?;
+        let runtime = Handle::current();
+        threads.push(spawn_blocking(move || {
+            runtime.block_on(read_file_and_send_data(
+                file_event_receiver,
+                client,
+                checkpoints,
+                ReadOptions::default(),
+                PodCache::default(),
+                Heartbeat::default(),
+                sig_term_clone,
+            ))?;
             debug!("Read and send thread finished");
             Ok(())
         }));
@@ -104,4 +135,95 @@ mod integration_tests {
 
         Ok(())
     }
+
+    // The blocking loops run on the blocking pool, a single worker is left for the handler
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_shutdown_drains_and_persists_offsets() -> Result<(), LogDaemonError> {
+        setup_tracing()?;
+        let mut threads: Vec<JoinHandle<Result<(), LogDaemonError>>> = Vec::new();
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoint_path = temp_path.join("checkpoints.json");
+        let log_dir = temp_path.join("pods");
+        create_dir(&log_dir)?;
+
+        let sig_term = Arc::new(AtomicBool::new(false));
+        let sig_term_clone = sig_term.clone();
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+        let signals = async move {
+            shutdown_receiver.await.ok();
+            Ok("SIGTERM")
+        };
+        let handler = tokio::spawn(handle_signals(signals, sig_term_clone));
+
+        let checkpoints = CheckpointStore::load(&checkpoint_path, Duration::from_secs(60))?;
+
+        // File events thread
+        let log_dir_clone = log_dir.clone();
+        let checkpoints_clone = checkpoints.clone();
+        let sig_term_clone = sig_term.clone();
+        let (file_event_sender, file_event_receiver) = mpsc::sync_channel(16);
+        threads.push(spawn_blocking(move || {
+            process_file_events(
+                &log_dir_clone,
+                file_event_sender,
+                LogFilter::default(),
+                checkpoints_clone,
+                WatchOptions::default(),
+                Heartbeat::default(),
+                sig_term_clone,
+            )?;
+            Ok(())
+        }));
+
+        // The last record of a file is held back until the flush timeout
+        let received_data = Arc::new(Mutex::new(Vec::new()));
+        let client = MockHik8sClient::new(Arc::clone(&received_data));
+        let options = ReadOptions {
+            output_format: "structured".parse().unwrap(),
+            multiline: Some(MultilinePattern::from_presets("java").unwrap()),
+            flush_timeout: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let sig_term_clone = sig_term.clone();
+        let runtime = Handle::current();
+        threads.push(spawn_blocking(move || {
+            runtime.block_on(read_file_and_send_data(
+                file_event_receiver,
+                client,
+                checkpoints,
+                options,
+                PodCache::default(),
+                Heartbeat::default(),
+                sig_term_clone,
+            ))?;
+            Ok(())
+        }));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let log_path = log_dir.join("0.log");
+        std::fs::File::create(&log_path)?;
+        write_to_existing_file(
+            &log_path,
+            "2024-01-01T00:00:00.000000000Z stderr F java.lang.IllegalStateException: boom\n\
+             2024-01-01T00:00:00.000000001Z stderr F \tat com.example.Main.run(Main.java:10)",
+        )?;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(received_data.lock().unwrap().is_empty());
+
+        shutdown_sender.send(()).unwrap();
+        handler.await??;
+        for thread in threads {
+            thread.await??;
+        }
+        assert!(sig_term.load(Ordering::SeqCst));
+
+        // The held back record was shipped and its offset persisted
+        assert_eq!(received_data.lock().unwrap().len(), 1);
+        let reloaded = CheckpointStore::load(&checkpoint_path, Duration::from_secs(60))?;
+        let checkpoint = reloaded.get(&log_path).unwrap();
+        assert_eq!(checkpoint.offset, std::fs::metadata(&log_path)?.len());
+        Ok(())
+    }
 }
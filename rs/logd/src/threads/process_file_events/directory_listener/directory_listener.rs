@@ -1,33 +1,111 @@
-use inotify::{Inotify, WatchMask};
+use inotify::{Event, EventMask, Inotify, WatchMask};
 use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fs;
+use std::io::{self, ErrorKind};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::Sender;
-use tracing::info;
+use std::sync::mpsc::SyncSender;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+use crate::checkpoint::CheckpointStore;
+use crate::constant::INOTIFY_SLEEP;
+use crate::filter::LogFilter;
+use crate::metrics::INOTIFY_OVERFLOWS;
 
 use super::error::DirectoryListenerError;
+use super::watcher::{has_unread_data, record_lag, FileWatcher, PathQueue};
 
 pub struct DirectoryListener {
     pub inotify: Inotify,
     pub watch_descriptors: HashMap<i32, PathBuf>,
-    sender: Sender<HashSet<PathBuf>>,
+    // watch descriptors of files, the others watch directories
+    file_watches: HashSet<i32>,
+    queue: PathQueue,
+    filter: LogFilter,
+    sleep: Duration,
 }
 
 impl DirectoryListener {
-    pub fn new(sender: Sender<HashSet<PathBuf>>) -> Result<Self, DirectoryListenerError> {
-        let inotify = Inotify::init()?;
+    pub fn new(
+        sender: SyncSender<HashSet<PathBuf>>,
+        filter: LogFilter,
+    ) -> Result<Self, DirectoryListenerError> {
+        let inotify = Inotify::init().map_err(DirectoryListenerError::Inotify)?;
         Ok(Self {
             inotify,
             watch_descriptors: HashMap::new(),
-            sender,
+            file_watches: HashSet::new(),
+            queue: PathQueue::new(sender),
+            filter,
+            sleep: INOTIFY_SLEEP,
         })
     }
 
+    pub fn with_sleep(mut self, sleep: Duration) -> Self {
+        self.sleep = sleep;
+        self
+    }
+
+    pub fn queue(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.queue.queue(paths);
+    }
+
+    pub fn flush(&mut self) -> Result<(), DirectoryListenerError> {
+        self.queue.flush()
+    }
+
     pub fn get_descriptor(&self, watch_descriptor_id: &i32) -> Option<&PathBuf> {
         self.watch_descriptors.get(watch_descriptor_id)
     }
 
-    pub fn add_watches(&mut self, path: &Path) -> Result<(), DirectoryListenerError> {
+    // Collects the paths of changed and deleted files, watches new directories and forgets
+    // watches that the kernel removed
+    pub fn handle_event(
+        &mut self,
+        event: Event<&OsStr>,
+        files: &mut HashSet<PathBuf>,
+    ) -> Result<(), DirectoryListenerError> {
+        let watch_descriptor_id = event.wd.get_watch_descriptor_id();
+        // sent after DELETE_SELF, the watched path is gone
+        if event.mask.contains(EventMask::IGNORED) {
+            self.file_watches.remove(&watch_descriptor_id);
+            if let Some(path) = self.watch_descriptors.remove(&watch_descriptor_id) {
+                debug!("Removed watch for {:?}", path);
+            }
+            return Ok(());
+        }
+        let Some(path) = event
+            .name
+            .and_then(|name| Some(self.get_descriptor(&watch_descriptor_id)?.join(name)))
+        else {
+            return Ok(());
+        };
+
+        // kubelet renames compressed rotations into place (0.log.<timestamp>.gz),
+        // deleted files are passed on so their unread tail is drained
+        if event.mask.intersects(
+            EventMask::CLOSE_WRITE | EventMask::MODIFY | EventMask::MOVED_TO | EventMask::DELETE,
+        ) {
+            files.insert(path.clone());
+        }
+        if event
+            .mask
+            .intersects(EventMask::CREATE | EventMask::MOVED_TO)
+            && path.is_dir()
+        {
+            self.add_watches(&path)?;
+        }
+        Ok(())
+    }
+}
+
+impl FileWatcher for DirectoryListener {
+    fn add_watches(&mut self, path: &Path) -> Result<(), DirectoryListenerError> {
+        if !self.filter.allows_path(path) {
+            debug!("Skipping filtered path {:?}", path);
+            return Ok(());
+        }
         if path.is_dir() {
             for entry in fs::read_dir(path)? {
                 let entry = entry?;
@@ -37,21 +115,133 @@ impl DirectoryListener {
         }
 
         info!("Adding watch for {:?}", path);
-        let watch = self.inotify.watches().add(
-            path,
-            WatchMask::MODIFY | WatchMask::CREATE | WatchMask::DELETE | WatchMask::CLOSE_WRITE,
-        )?;
+        let watch = self
+            .inotify
+            .watches()
+            .add(
+                path,
+                WatchMask::MODIFY
+                    | WatchMask::CREATE
+                    | WatchMask::DELETE
+                    | WatchMask::DELETE_SELF
+                    | WatchMask::CLOSE_WRITE
+                    | WatchMask::MOVED_TO,
+            )
+            .map_err(watch_error)?;
 
+        let watch_descriptor_id = watch.get_watch_descriptor_id();
         self.watch_descriptors
-            .insert(watch.get_watch_descriptor_id(), path.to_path_buf());
+            .insert(watch_descriptor_id, path.to_path_buf());
 
         // Send the path of the file that was added
         if path.is_file() {
-            let mut paths = HashSet::new();
-            paths.insert(path.to_path_buf());
-            self.sender.send(paths)?;
+            self.file_watches.insert(watch_descriptor_id);
+            self.queue([path.to_path_buf()]);
         }
 
-        Ok(())
+        self.flush()
+    }
+
+    fn watch(&mut self) -> Result<bool, DirectoryListenerError> {
+        // buffer for reading close write events
+        // fits 25.6 events (40 bytes per event)
+        let mut buffer = [0; 65536];
+        let events = match self.inotify.read_events(&mut buffer) {
+            Ok(events) => events,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                // No events found, send paths that did not fit into the channel
+                self.flush()?;
+                std::thread::sleep(self.sleep);
+                return Ok(false);
+            }
+            Err(e) => return Err(DirectoryListenerError::Inotify(e)),
+        };
+
+        let mut files = HashSet::new();
+        let mut overflow = false;
+        for event in events {
+            if event.mask.contains(EventMask::Q_OVERFLOW) {
+                warn!("Event queue overflowed; some events may have been lost");
+                INOTIFY_OVERFLOWS.inc();
+                overflow = true;
+            }
+            match self.handle_event(event, &mut files) {
+                Ok(()) => {}
+                Err(e @ DirectoryListenerError::Inotify(_)) => return Err(e),
+                Err(e) => error!("{e}"),
+            }
+        }
+        self.queue(files);
+        self.flush()?;
+        Ok(overflow)
+    }
+
+    // Reconciles the watches and the checkpoints with the tree after events may have been
+    // lost. Adds missing watches, forgets watches of deleted paths and queues files with
+    // unread data.
+    fn rescan(
+        &mut self,
+        base_path: &Path,
+        checkpoints: &CheckpointStore,
+    ) -> Result<usize, DirectoryListenerError> {
+        // deleted files are passed on like a DELETE event, so the read thread drains and
+        // forgets them
+        let deleted: Vec<PathBuf> = self
+            .file_watches
+            .iter()
+            .filter_map(|wd| self.watch_descriptors.get(wd))
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect();
+        let mut queued = deleted.len();
+        self.queue(deleted);
+        self.watch_descriptors.retain(|_, path| path.exists());
+        self.file_watches
+            .retain(|wd| self.watch_descriptors.contains_key(wd));
+        let watched: HashSet<PathBuf> = self.watch_descriptors.values().cloned().collect();
+        let mut stack = vec![base_path.to_path_buf()];
+        while let Some(path) = stack.pop() {
+            if !self.filter.allows_path(&path) {
+                continue;
+            }
+            if !watched.contains(&path) {
+                // queues the files below a new directory
+                self.add_watches(&path)?;
+                continue;
+            }
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                for entry in fs::read_dir(&path)? {
+                    stack.push(entry?.path());
+                }
+                continue;
+            }
+            record_lag(checkpoints, &path, &metadata);
+            if has_unread_data(checkpoints, &path, &metadata) {
+                self.queue([path]);
+                queued += 1;
+            }
+        }
+        queued += self
+            .watch_descriptors
+            .values()
+            .filter(|path| !watched.contains(*path) && path.is_file())
+            .count();
+        self.flush()?;
+        Ok(queued)
+    }
+
+    fn files_watched(&self) -> usize {
+        self.file_watches.len()
+    }
+}
+
+// Watches of files deleted in the meantime are not an inotify failure
+fn watch_error(e: io::Error) -> DirectoryListenerError {
+    match e.kind() {
+        ErrorKind::NotFound => DirectoryListenerError::Io(e),
+        _ => DirectoryListenerError::Inotify(e),
     }
 }
@@ -6,6 +6,8 @@ use thiserror::Error;
 pub enum DirectoryListenerError {
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
+    #[error("Inotify error: {0}")]
+    Inotify(io::Error),
     #[error("Failed to send path: {0:?}")]
     Send(#[from] SendError<HashSet<PathBuf>>),
 }
@@ -1,5 +1,9 @@
 mod directory_listener;
 mod error;
+mod poll_listener;
+mod watcher;
 
 pub use directory_listener::DirectoryListener;
 pub use error::DirectoryListenerError;
+pub use poll_listener::PollListener;
+pub use watcher::{FileWatcher, WatchMode, WatchOptions};
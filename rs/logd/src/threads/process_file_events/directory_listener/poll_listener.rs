@@ -0,0 +1,171 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, Metadata};
+use std::io::ErrorKind;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::SyncSender;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, info, warn};
+
+use crate::checkpoint::CheckpointStore;
+use crate::filter::LogFilter;
+
+use super::error::DirectoryListenerError;
+use super::watcher::{has_unread_data, record_lag, FileWatcher, PathQueue};
+
+// Stat based watcher for nodes where inotify is unavailable or out of watches. Every poll
+// walks the watched trees and compares inode, size and modification time of each file
+// with the previous poll.
+pub struct PollListener {
+    roots: Vec<PathBuf>,
+    files: HashMap<PathBuf, FileState>,
+    queue: PathQueue,
+    filter: LogFilter,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FileState {
+    ino: u64,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl From<&Metadata> for FileState {
+    fn from(metadata: &Metadata) -> Self {
+        Self {
+            ino: metadata.ino(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+impl PollListener {
+    pub fn new(
+        sender: SyncSender<HashSet<PathBuf>>,
+        filter: LogFilter,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            roots: Vec::new(),
+            files: HashMap::new(),
+            queue: PathQueue::new(sender),
+            filter,
+            interval,
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Records the state of the files below the path, new and changed files go into changed.
+    // Only errors of the path itself are returned, paths below it that cannot be read are
+    // skipped.
+    fn scan(
+        &mut self,
+        path: &Path,
+        seen: &mut HashSet<PathBuf>,
+        changed: &mut HashSet<PathBuf>,
+    ) -> Result<(), DirectoryListenerError> {
+        if !self.filter.allows_path(path) {
+            return Ok(());
+        }
+        // deleted since it was listed
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if metadata.is_dir() {
+            let entries = match fs::read_dir(path) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+            for entry in entries {
+                // e.g. a pod directory deleted or made unreadable while it is scanned
+                let result = entry
+                    .map_err(DirectoryListenerError::from)
+                    .and_then(|entry| self.scan(&entry.path(), seen, changed));
+                if let Err(e) = result {
+                    warn!("Skipping entry of {} while polling: {e}", path.display());
+                }
+            }
+            return Ok(());
+        }
+        let state = FileState::from(&metadata);
+        seen.insert(path.to_path_buf());
+        if self.files.insert(path.to_path_buf(), state) != Some(state) {
+            changed.insert(path.to_path_buf());
+        }
+        Ok(())
+    }
+}
+
+impl FileWatcher for PollListener {
+    fn add_watches(&mut self, path: &Path) -> Result<(), DirectoryListenerError> {
+        if !self.filter.allows_path(path) {
+            debug!("Skipping filtered path {:?}", path);
+            return Ok(());
+        }
+        info!("Polling {:?} every {:?}", path, self.interval);
+        let mut changed = HashSet::new();
+        self.scan(path, &mut HashSet::new(), &mut changed)?;
+        self.roots.push(path.to_path_buf());
+        self.queue.queue(changed);
+        self.queue.flush()
+    }
+
+    fn watch(&mut self) -> Result<bool, DirectoryListenerError> {
+        let elapsed = self.last_poll.elapsed();
+        if elapsed < self.interval {
+            self.queue.flush()?;
+            std::thread::sleep((self.interval - elapsed).min(Duration::from_millis(100)));
+            return Ok(false);
+        }
+        self.last_poll = Instant::now();
+
+        let mut seen = HashSet::new();
+        let mut changed = HashSet::new();
+        for root in self.roots.clone() {
+            self.scan(&root, &mut seen, &mut changed)?;
+        }
+        // deleted files are passed on so their unread tail is drained
+        self.files.retain(|path, _| {
+            let exists = seen.contains(path);
+            if !exists {
+                changed.insert(path.clone());
+            }
+            exists
+        });
+        self.queue.queue(changed);
+        self.queue.flush()?;
+        Ok(false)
+    }
+
+    // Queues the known files below the path whose size differs from their checkpoint
+    fn rescan(
+        &mut self,
+        base_path: &Path,
+        checkpoints: &CheckpointStore,
+    ) -> Result<usize, DirectoryListenerError> {
+        let mut unread = HashSet::new();
+        for path in self.files.keys().filter(|path| path.starts_with(base_path)) {
+            let Ok(metadata) = fs::metadata(path) else {
+                continue;
+            };
+            record_lag(checkpoints, path, &metadata);
+            if has_unread_data(checkpoints, path, &metadata) {
+                unread.insert(path.clone());
+            }
+        }
+        let queued = unread.len();
+        self.queue.queue(unread);
+        self.queue.flush()?;
+        Ok(queued)
+    }
+
+    fn files_watched(&self) -> usize {
+        self.files.len()
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{SendError, SyncSender, TrySendError};
+use std::time::Duration;
+
+use crate::checkpoint::CheckpointStore;
+use crate::constant::{INOTIFY_SLEEP, POLL_INTERVAL};
+use crate::metrics::{CHANNEL_DEPTH, FILE_LAG};
+use crate::threads::read_and_send::is_log_file;
+
+use super::error::DirectoryListenerError;
+
+// Finds the log files that changed and sends their paths to the read thread
+pub trait FileWatcher {
+    // Watches the path and the files below it, the files are queued to be read
+    fn add_watches(&mut self, path: &Path) -> Result<(), DirectoryListenerError>;
+
+    // Waits briefly for changes and sends the changed paths.
+    // Returns true if changes may have been lost.
+    fn watch(&mut self) -> Result<bool, DirectoryListenerError>;
+
+    // Reconciles the watches and the checkpoints with the tree. Returns the number of
+    // files with unread data that were queued.
+    fn rescan(
+        &mut self,
+        base_path: &Path,
+        checkpoints: &CheckpointStore,
+    ) -> Result<usize, DirectoryListenerError>;
+
+    fn files_watched(&self) -> usize;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WatchMode {
+    // inotify, polling if inotify fails
+    #[default]
+    Auto,
+    Inotify,
+    Poll,
+}
+
+impl FromStr for WatchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "inotify" => Ok(Self::Inotify),
+            "poll" => Ok(Self::Poll),
+            other => Err(format!("unknown watch mode: {other}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct WatchOptions {
+    pub mode: WatchMode,
+    // Time between two scans of the tree in the poll mode
+    pub poll_interval: Duration,
+    // Time inotify is not read again after it had no events
+    pub inotify_sleep: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            mode: WatchMode::default(),
+            poll_interval: POLL_INTERVAL,
+            inotify_sleep: INOTIFY_SLEEP,
+        }
+    }
+}
+
+// Paths waiting for room in the channel to the read thread
+pub struct PathQueue {
+    sender: SyncSender<HashSet<PathBuf>>,
+    // paths not sent yet because the reader is behind, merged with later events
+    pending: HashSet<PathBuf>,
+}
+
+impl PathQueue {
+    pub fn new(sender: SyncSender<HashSet<PathBuf>>) -> Self {
+        Self {
+            sender,
+            pending: HashSet::new(),
+        }
+    }
+
+    pub fn queue(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.pending.extend(paths);
+    }
+
+    // Sends the queued paths if the channel has room. Otherwise they stay queued, so a burst
+    // of events for the same files is coalesced instead of buffered in the channel.
+    pub fn flush(&mut self) -> Result<(), DirectoryListenerError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        match self.sender.try_send(std::mem::take(&mut self.pending)) {
+            Ok(()) => {
+                CHANNEL_DEPTH.with_label_values(&["file_events"]).inc();
+                Ok(())
+            }
+            Err(TrySendError::Full(paths)) => {
+                self.pending = paths;
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(paths)) => Err(SendError(paths).into()),
+        }
+    }
+}
+
+// Only live log files are compared with their checkpoint. Offsets of .gz files count
+// decompressed bytes, and files that are not logs are never shipped.
+fn is_tracked(path: &Path) -> bool {
+    is_log_file(path)
+        && !matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("gz" | "tmp")
+        )
+}
+
+// Records the bytes after the committed offset, the whole file without a checkpoint of the
+// same inode
+pub fn record_lag(checkpoints: &CheckpointStore, path: &Path, metadata: &Metadata) {
+    if !is_tracked(path) {
+        return;
+    }
+    let lag = match checkpoints.get(path) {
+        Some(checkpoint) if checkpoint.matches(metadata) => {
+            metadata.len().saturating_sub(checkpoint.offset)
+        }
+        _ => metadata.len(),
+    };
+    FILE_LAG
+        .with_label_values(&[&path.to_string_lossy()])
+        .set(lag as i64);
+}
+
+// A file has unread data if it grew or shrank since its checkpoint, or if it has no
+// checkpoint of the same inode and is not empty
+pub fn has_unread_data(checkpoints: &CheckpointStore, path: &Path, metadata: &Metadata) -> bool {
+    if !is_tracked(path) {
+        return false;
+    }
+    match checkpoints.get(path) {
+        Some(checkpoint) if checkpoint.matches(metadata) => metadata.len() != checkpoint.offset,
+        _ => metadata.len() > 0,
+    }
+}
@@ -3,5 +3,6 @@ mod error;
 mod process_file_events;
 mod test;
 
+pub use directory_listener::{WatchMode, WatchOptions};
 pub use error::EventThreadError;
 pub use process_file_events::process_file_events;
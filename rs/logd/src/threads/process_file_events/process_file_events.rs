@@ -1,9 +1,10 @@
-use inotify::EventMask;
+use shared::health::Heartbeat;
 use std::collections::HashSet;
-use std::io::ErrorKind;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tracing::debug;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
 
 use std::path::Path;
 use std::path::PathBuf;
@@ -12,83 +13,101 @@ use std::sync::atomic::Ordering;
 use std::sync::mpsc;
 use std::sync::Arc;
 
+use crate::checkpoint::CheckpointStore;
+use crate::constant::RESCAN_INTERVAL;
+use crate::filter::LogFilter;
+use crate::metrics::FILES_WATCHED;
+
 use super::error::EventThreadError;
 
-use super::directory_listener::DirectoryListener;
+use super::directory_listener::{
+    DirectoryListener, DirectoryListenerError, FileWatcher, PollListener, WatchMode, WatchOptions,
+};
 
 pub fn process_file_events(
     base_path: &Path,
-    sender: mpsc::Sender<HashSet<PathBuf>>,
+    sender: mpsc::SyncSender<HashSet<PathBuf>>,
+    filter: LogFilter,
+    checkpoints: CheckpointStore,
+    options: WatchOptions,
+    heartbeat: Heartbeat,
     termination_signal: Arc<AtomicBool>,
 ) -> Result<(), EventThreadError> {
     info!("Starting process_file_events thread...");
-    // buffer for reading close write events
-    // fits 25.6 events (40 bytes per event)
-    let mut buffer = [0; 65536];
 
     // Add a watch for each file in the directory
-    let mut listener = DirectoryListener::new(sender.clone())?;
-    listener.add_watches(base_path)?;
+    let mut watcher = create_watcher(base_path, &sender, &filter, options)?;
+    let mut last_rescan = Instant::now();
 
     loop {
+        heartbeat.beat();
         if termination_signal.load(Ordering::SeqCst) {
             // this sleep allows receiver to close the channel
             std::thread::sleep(Duration::from_millis(500));
             break;
         }
-        let events: inotify::Events<'_> = match listener.inotify.read_events(&mut buffer) {
-            Ok(events) => events,
+        let overflow = match watcher.watch() {
+            Ok(overflow) => overflow,
+            Err(DirectoryListenerError::Inotify(e)) if options.mode == WatchMode::Auto => {
+                warn!("Inotify failed, falling back to polling: {e}");
+                let poll = WatchOptions {
+                    mode: WatchMode::Poll,
+                    ..options
+                };
+                watcher = create_watcher(base_path, &sender, &filter, poll)?;
+                continue;
+            }
             Err(e) => {
-                if e.kind() == ErrorKind::WouldBlock {
-                    // No events found
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                    continue;
-                } else {
-                    error!("{}", EventThreadError::IoError(e));
-                    std::thread::sleep(std::time::Duration::from_secs(10));
-                    continue;
-                }
+                error!("{}", EventThreadError::DirectoryListener(e));
+                std::thread::sleep(std::time::Duration::from_secs(10));
+                continue;
             }
         };
+        FILES_WATCHED.set(watcher.files_watched() as i64);
+        if overflow || last_rescan.elapsed() >= RESCAN_INTERVAL {
+            rescan(watcher.as_mut(), base_path, &checkpoints);
+            last_rescan = Instant::now();
+        }
+    }
+    Ok(())
+}
 
-        let mut files = HashSet::new();
-        for event in events {
-            if event.mask.contains(EventMask::Q_OVERFLOW) {
-                tracing::warn!("Event queue overflowed; some events may have been lost");
-            }
-            if event.mask.contains(EventMask::CLOSE_WRITE) || event.mask.contains(EventMask::MODIFY)
-            {
-                if let Some(name) = event.name {
-                    if let Some(dir_path) =
-                        listener.get_descriptor(&event.wd.get_watch_descriptor_id())
-                    {
-                        let path = dir_path.join(name);
-                        files.insert(path);
-                    }
-                }
-            }
-            if event.mask.contains(EventMask::CREATE) {
-                if let Some(name) = event.name {
-                    if let Some(dir_path) =
-                        listener.get_descriptor(&event.wd.get_watch_descriptor_id())
-                    {
-                        let path = dir_path.join(name);
-                        if path.is_dir() {
-                            listener
-                                .add_watches(&path)
-                                .map_err(EventThreadError::DirectoryListener)
-                                .inspect_err(|e| error!("{e}"))
-                                .ok();
-                        }
-                    }
-                }
+// Watches with inotify unless polling is selected. In the auto mode, inotify errors while
+// the tree is watched fall back to polling.
+fn create_watcher(
+    base_path: &Path,
+    sender: &mpsc::SyncSender<HashSet<PathBuf>>,
+    filter: &LogFilter,
+    options: WatchOptions,
+) -> Result<Box<dyn FileWatcher>, EventThreadError> {
+    if options.mode != WatchMode::Poll {
+        let listener =
+            DirectoryListener::new(sender.clone(), filter.clone()).and_then(|listener| {
+                let mut listener = listener.with_sleep(options.inotify_sleep);
+                listener.add_watches(base_path)?;
+                Ok(listener)
+            });
+        match listener {
+            Ok(listener) => return Ok(Box::new(listener)),
+            Err(DirectoryListenerError::Inotify(e)) if options.mode == WatchMode::Auto => {
+                warn!("Inotify failed, falling back to polling: {e}");
             }
+            Err(e) => return Err(e.into()),
         }
-        sender
-            .send(files)
-            .map_err(EventThreadError::SendError)
-            .inspect_err(|e| error!("{e}"))
-            .ok();
     }
-    Ok(())
+    let mut listener = PollListener::new(sender.clone(), filter.clone(), options.poll_interval);
+    listener.add_watches(base_path)?;
+    Ok(Box::new(listener))
+}
+
+fn rescan(watcher: &mut dyn FileWatcher, base_path: &Path, checkpoints: &CheckpointStore) {
+    match watcher.rescan(base_path, checkpoints) {
+        Ok(0) => debug!("Rescan of {} found no unread files", base_path.display()),
+        Ok(queued) => info!(
+            "Rescan of {} queued {} files with unread data",
+            base_path.display(),
+            queued
+        ),
+        Err(e) => error!("{}", EventThreadError::DirectoryListener(e)),
+    }
 }
@@ -1,14 +1,24 @@
 #[cfg(test)]
 mod integration_tests {
 
+    use std::collections::HashSet;
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::{mpsc, Arc};
     use std::time::Duration;
     use tempfile::tempdir;
     use tokio::task::JoinHandle;
 
-    use crate::threads::process_file_events::{process_file_events, EventThreadError};
+    use crate::checkpoint::CheckpointStore;
+    use crate::filter::LogFilter;
+    use crate::metrics::FILE_LAG;
+    use crate::threads::process_file_events::directory_listener::{
+        DirectoryListener, FileWatcher, PollListener,
+    };
+    use crate::threads::process_file_events::{
+        process_file_events, EventThreadError, WatchOptions,
+    };
     use crate::util::test::test_util::{create_test_file, write_to_existing_file};
+    use shared::health::Heartbeat;
     use shared::tracing::setup_tracing;
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -25,13 +35,23 @@ mod integration_tests {
         let termination_signal_clone = Arc::clone(&termination_signal);
 
         // Create a channel for communication
-        let (sender, receiver) = mpsc::channel();
+        let (sender, receiver) = mpsc::sync_channel(16);
 
         // Spawn a thread to run the process_file_events function
         let temp_path_clone = temp_path.clone();
+        let checkpoints_dir = tempdir().expect("Failed to create temp dir");
+        let checkpoints_path = checkpoints_dir.path().join("checkpoints.json");
         let mut threads: Vec<JoinHandle<Result<(), EventThreadError>>> = Vec::new();
         threads.push(tokio::spawn(async move {
-            process_file_events(&temp_path_clone, sender, termination_signal_clone)?;
+            process_file_events(
+                &temp_path_clone,
+                sender,
+                LogFilter::default(),
+                CheckpointStore::load(&checkpoints_path, Duration::from_secs(60)).unwrap(),
+                WatchOptions::default(),
+                Heartbeat::default(),
+                termination_signal_clone,
+            )?;
             Ok(())
         }));
 
@@ -69,4 +89,251 @@ mod integration_tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_directory_listener_skips_filtered_paths() -> Result<(), EventThreadError> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let temp_path = temp_dir.path().to_path_buf();
+        let system_path = temp_path.join("kube-system_coredns_1f5c6f3e/coredns");
+        let sidecar_path = temp_path.join("default_web_4a8e9c55/istio-proxy");
+        let web_path = temp_path.join("default_web_4a8e9c55/web");
+        for path in [&system_path, &sidecar_path, &web_path] {
+            std::fs::create_dir_all(path)?;
+            create_test_file(path, "0.log")?;
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let filter = LogFilter::new("", "namespace=kube-system, container=istio-proxy").unwrap();
+        let mut listener = DirectoryListener::new(sender, filter)?;
+        listener.add_watches(&temp_path)?;
+
+        let paths: Vec<_> = receiver.try_iter().flatten().collect();
+        assert_eq!(paths, vec![web_path.join("0.log")]);
+        let watched: Vec<_> = listener.watch_descriptors.values().collect();
+        assert!(!watched.contains(&&system_path));
+        assert!(!watched.contains(&&sidecar_path));
+        assert!(watched.contains(&&web_path));
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_listener_coalesces_when_channel_is_full() -> Result<(), EventThreadError> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let temp_path = temp_dir.path().to_path_buf();
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let mut listener = DirectoryListener::new(sender, LogFilter::default())?;
+
+        listener.queue([temp_path.join("0.log")]);
+        listener.flush()?;
+
+        // The reader is behind, events for the same file are merged
+        for _ in 0..3 {
+            listener.queue([temp_path.join("1.log")]);
+            listener.flush()?;
+        }
+        listener.queue([temp_path.join("2.log")]);
+        listener.flush()?;
+
+        assert_eq!(receiver.try_recv().unwrap().len(), 1);
+        assert!(receiver.try_recv().is_err());
+        listener.flush()?;
+        let paths = receiver.try_recv().unwrap();
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&temp_path.join("1.log")));
+        assert!(paths.contains(&temp_path.join("2.log")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_listener_forgets_deleted_paths() -> Result<(), EventThreadError> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let temp_path = temp_dir.path().to_path_buf();
+        let pod_path = temp_path.join("default_web_4a8e9c55");
+        let container_path = pod_path.join("web");
+        std::fs::create_dir_all(&container_path)?;
+        let file_path = create_test_file(&container_path, "0.log")?;
+
+        let (sender, _receiver) = mpsc::sync_channel(16);
+        let mut listener = DirectoryListener::new(sender, LogFilter::default())?;
+        listener.add_watches(&temp_path)?;
+        assert_eq!(listener.watch_descriptors.len(), 4);
+
+        std::fs::remove_dir_all(&pod_path)?;
+        let mut buffer = [0; 4096];
+        let mut files = HashSet::new();
+        let start = std::time::Instant::now();
+        while listener.watch_descriptors.len() > 1 && start.elapsed() < Duration::from_secs(1) {
+            if let Ok(events) = listener.inotify.read_events(&mut buffer) {
+                for event in events {
+                    listener.handle_event(event, &mut files)?;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // Only the root is still watched, the deleted file is passed on to be drained
+        let watched: Vec<_> = listener.watch_descriptors.values().collect();
+        assert_eq!(watched, vec![&temp_path]);
+        assert!(files.contains(&file_path));
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_listener_rescan() -> Result<(), EventThreadError> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let temp_path = temp_dir.path().join("pods");
+        std::fs::create_dir(&temp_path)?;
+        let read_path = create_test_file(&temp_path, "read.log")?;
+        let unread_path = create_test_file(&temp_path, "unread.log")?;
+
+        let checkpoints = CheckpointStore::load(
+            &temp_dir.path().join("checkpoints.json"),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        for path in [&read_path, &unread_path] {
+            let metadata = std::fs::metadata(path)?;
+            checkpoints.commit(path, &metadata, metadata.len());
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let mut listener = DirectoryListener::new(sender, LogFilter::default())?;
+        listener.add_watches(&temp_path)?;
+        receiver.try_iter().for_each(drop);
+
+        // Events for these changes were lost
+        write_to_existing_file(&unread_path, "This line was not read.")?;
+        let new_dir = temp_path.join("new");
+        std::fs::create_dir(&new_dir)?;
+        let new_path = create_test_file(&new_dir, "0.log")?;
+
+        assert_eq!(listener.rescan(&temp_path, &checkpoints)?, 2);
+        let paths: HashSet<_> = receiver.try_iter().flatten().collect();
+        assert_eq!(paths, HashSet::from([unread_path, new_path]));
+        assert!(listener
+            .watch_descriptors
+            .values()
+            .any(|path| path == &new_dir));
+
+        // Nothing is left to read
+        let metadata = std::fs::metadata(temp_path.join("unread.log"))?;
+        checkpoints.commit(&temp_path.join("unread.log"), &metadata, metadata.len());
+        let metadata = std::fs::metadata(new_dir.join("0.log"))?;
+        checkpoints.commit(&new_dir.join("0.log"), &metadata, metadata.len());
+        assert_eq!(listener.rescan(&temp_path, &checkpoints)?, 0);
+
+        // The event of a deleted file was lost
+        std::fs::remove_file(&read_path)?;
+        assert_eq!(listener.rescan(&temp_path, &checkpoints)?, 1);
+        let paths: HashSet<_> = receiver.try_iter().flatten().collect();
+        assert_eq!(paths, HashSet::from([read_path.clone()]));
+        assert!(!listener
+            .watch_descriptors
+            .values()
+            .any(|path| path == &read_path));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rescan_skips_compressed_and_docker_state_files() -> Result<(), EventThreadError> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let temp_path = temp_dir.path().join("containers");
+        let container_path = temp_path.join("a".repeat(64));
+        std::fs::create_dir_all(&container_path)?;
+        let state_path = create_test_file(&container_path, "config.v2.json")?;
+        let rotated_path = create_test_file(&temp_path, "0.log.20240101-000000.gz")?;
+
+        // The offset of the shipped .gz file counts decompressed bytes
+        let checkpoints = CheckpointStore::load(
+            &temp_dir.path().join("checkpoints.json"),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        let metadata = std::fs::metadata(&rotated_path)?;
+        checkpoints.commit(&rotated_path, &metadata, metadata.len() * 4);
+
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let mut listener = DirectoryListener::new(sender, LogFilter::default())?;
+        listener.add_watches(&temp_path)?;
+        receiver.try_iter().for_each(drop);
+
+        assert_eq!(listener.rescan(&temp_path, &checkpoints)?, 0);
+        assert_eq!(receiver.try_iter().count(), 0);
+        for path in [&state_path, &rotated_path] {
+            assert!(FILE_LAG
+                .get_metric_with_label_values(&[&path.to_string_lossy()])
+                .is_ok_and(|gauge| gauge.get() == 0));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_poll_listener_detects_changes() -> Result<(), EventThreadError> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let temp_path = temp_dir.path().to_path_buf();
+        let file1_path = create_test_file(&temp_path, "file1")?;
+
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let mut listener = PollListener::new(sender, LogFilter::default(), Duration::ZERO);
+        listener.add_watches(&temp_path)?;
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            HashSet::from([file1_path.clone()])
+        );
+
+        // Nothing changed
+        assert!(!listener.watch()?);
+        assert!(receiver.try_recv().is_err());
+
+        // Appended and new files in new directories are picked up
+        write_to_existing_file(&file1_path, "This line was appended.")?;
+        let subdir_path = temp_path.join("subdir");
+        std::fs::create_dir(&subdir_path)?;
+        let file2_path = create_test_file(&subdir_path, "file2")?;
+        listener.watch()?;
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            HashSet::from([file1_path.clone(), file2_path])
+        );
+
+        // Deleted files are passed on once
+        std::fs::remove_file(&file1_path)?;
+        listener.watch()?;
+        assert_eq!(receiver.try_recv().unwrap(), HashSet::from([file1_path]));
+        listener.watch()?;
+        assert!(receiver.try_recv().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_poll_listener_skips_unreadable_entries() -> Result<(), EventThreadError> {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let temp_path = temp_dir.path().to_path_buf();
+        let pod_path = temp_path.join("pod");
+        std::fs::create_dir(&pod_path)?;
+        // stat fails with ELOOP, not NotFound
+        std::os::unix::fs::symlink(pod_path.join("loop"), pod_path.join("loop"))?;
+        let file_path = create_test_file(&temp_path, "0.log")?;
+
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let mut listener = PollListener::new(sender, LogFilter::default(), Duration::ZERO);
+        listener.add_watches(&temp_path)?;
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            HashSet::from([file_path.clone()])
+        );
+
+        write_to_existing_file(&file_path, "This line was polled.")?;
+        listener.watch()?;
+        assert_eq!(receiver.try_recv().unwrap(), HashSet::from([file_path]));
+
+        // The root itself is still an error
+        let mut listener = PollListener::new(
+            mpsc::sync_channel(16).0,
+            LogFilter::default(),
+            Duration::ZERO,
+        );
+        assert!(listener.add_watches(&pod_path.join("loop")).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,57 @@
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::constant::MAX_INFLIGHT_BYTES;
+
+// Bytes read from files or the spool that are not uploaded or spooled yet. Readers wait for
+// the budget before they read a chunk, so a burst of logs pauses reading instead of growing
+// memory.
+#[derive(Clone)]
+pub struct ByteBudget {
+    semaphore: Arc<Semaphore>,
+    capacity: usize,
+}
+
+impl ByteBudget {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.clamp(1, u32::MAX as usize);
+        Self {
+            semaphore: Arc::new(Semaphore::new(capacity)),
+            capacity,
+        }
+    }
+
+    // The bytes are returned when the permit is dropped. A request larger than the budget
+    // waits for the whole budget.
+    pub async fn acquire(&self, bytes: usize) -> OwnedSemaphorePermit {
+        let bytes = bytes.clamp(1, self.capacity) as u32;
+        Arc::clone(&self.semaphore)
+            .acquire_many_owned(bytes)
+            .await
+            .expect("byte budget semaphore is never closed")
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}
+
+impl Default for ByteBudget {
+    fn default() -> Self {
+        Self::new(MAX_INFLIGHT_BYTES)
+    }
+}
+
+impl fmt::Debug for ByteBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ByteBudget")
+            .field("capacity", &self.capacity)
+            .field("available", &self.available())
+            .finish()
+    }
+}
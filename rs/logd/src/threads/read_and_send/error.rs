@@ -6,6 +6,9 @@ use shared::{
     tracing::TracingSetupError,
 };
 
+use crate::checkpoint::CheckpointError;
+use crate::spool::SpoolError;
+
 use super::reader::ReaderError;
 
 #[derive(Error, Debug)]
@@ -14,6 +17,8 @@ pub enum ReadThreadError {
     IoError(#[from] io::Error),
     #[error("Hik8s client error: {0}")]
     Hik8sClient(#[from] Hik8sClientError),
+    #[error("Upload rejected: {0}")]
+    Rejected(Hik8sClientError),
     #[error("Tracing setup error: {0}")]
     TracingSetup(#[from] TracingSetupError),
     #[error("Channel receive timeout: {0}")]
@@ -22,4 +27,10 @@ pub enum ReadThreadError {
     FormData(#[from] FormDataError),
     #[error("Reader error: {0}")]
     Reader(#[from] ReaderError),
+    #[error("Checkpoint error: {0}")]
+    Checkpoint(#[from] CheckpointError),
+    #[error("Spool error: {0}")]
+    Spool(#[from] SpoolError),
+    #[error("Json serialize error: {0}")]
+    Json(#[from] serde_json::Error),
 }
@@ -0,0 +1,23 @@
+use serde::Serialize;
+
+use crate::pods::PodInfo;
+
+use super::source::PodLogSource;
+
+// The metadata part of an upload
+#[derive(Debug, Serialize)]
+pub struct UploadMetadata {
+    // directory of the file
+    pub path: String,
+    pub file: String,
+    pub format: String,
+    // Content-Encoding of the stream part
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<&'static str>,
+    // absent if the path is not in the kubelet layout
+    #[serde(flatten)]
+    pub source: Option<PodLogSource>,
+    // labels, annotations, owners and images of the pod, if enrichment is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod: Option<PodInfo>,
+}
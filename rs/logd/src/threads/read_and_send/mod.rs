@@ -1,7 +1,16 @@
+mod budget;
 mod error;
+mod metadata;
+mod options;
 mod read_and_send;
 mod reader;
+mod retry;
+mod rotation;
+mod source;
 mod test;
 
 pub use error::ReadThreadError;
+pub use options::ReadOptions;
 pub use read_and_send::read_file_and_send_data;
+pub use reader::{MultilinePattern, RedactionRuleConfig};
+pub use source::is_log_file;
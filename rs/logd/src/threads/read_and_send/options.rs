@@ -0,0 +1,194 @@
+use shared::client::Compression;
+use std::fmt::Display;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::{Config, ConfigError};
+use crate::constant::{
+    BATCH_SIZE, ENV_COMPRESSION, ENV_DEDUP, ENV_EXCLUDE, ENV_INCLUDE, ENV_MULTILINE,
+    ENV_MULTILINE_START_PATTERN, ENV_OUTPUT_FORMAT, ENV_REDACT, ENV_WATCH_MODE, HIK8S_ROUTE_LOG,
+    MULTILINE_FLUSH_TIMEOUT, RECV_TIMEOUT, SHUTDOWN_GRACE_PERIOD,
+};
+
+use crate::filter::LogFilter;
+use crate::spool::SpoolOptions;
+use crate::threads::process_file_events::{WatchMode, WatchOptions};
+
+use super::budget::ByteBudget;
+use super::reader::{
+    DedupOptions, MultilinePattern, OutputFormat, RateLimit, RateLimits, Redactor,
+};
+
+#[derive(Clone, Debug)]
+pub struct ReadOptions {
+    // Decompress and ship rotated .gz files that were not fully shipped before
+    pub ship_compressed: bool,
+    pub output_format: OutputFormat,
+    // Joins the lines of stack traces into one record, structured format only
+    pub multiline: Option<MultilinePattern>,
+    // Records held back longer than this are shipped even if the next record did not start
+    pub flush_timeout: Duration,
+    // Namespaces, pods and containers to ship
+    pub filter: LogFilter,
+    // Replaces secrets and personal data before upload
+    pub redactor: Redactor,
+    // Codec of the uploaded stream, disabled if the server rejects it
+    pub compression: Compression,
+    // Attach the labels, annotations, owners and images of the pod to uploads
+    pub pod_enrichment: bool,
+    // Honor the hik8s.ai/ annotations of pods
+    pub pod_annotations: bool,
+    // Chunks that fail to upload are kept on disk and replayed, retried in place if unset
+    pub spool: Option<SpoolOptions>,
+    // Lines over the node and container limits are dropped and reported in a marker record
+    pub rate_limits: RateLimits,
+    // Collapses repeated records into one with a repeat count, structured format only
+    pub dedup: Option<DedupOptions>,
+    // How the file events thread finds changed files, inotify or polling
+    pub watch: WatchOptions,
+    // Time to ship pending files after the termination signal
+    pub grace_period: Duration,
+    // Route of the upload endpoint
+    pub route: String,
+    // Bytes read from a file per upload
+    pub batch_size: usize,
+    // Bytes read and not yet delivered, reading pauses while they are used up
+    pub budget: ByteBudget,
+    // Time to wait for file events before retries and timed out records are handled
+    pub recv_timeout: Duration,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            ship_compressed: false,
+            output_format: OutputFormat::default(),
+            multiline: None,
+            flush_timeout: MULTILINE_FLUSH_TIMEOUT,
+            filter: LogFilter::default(),
+            redactor: Redactor::default(),
+            compression: Compression::default(),
+            pod_enrichment: false,
+            pod_annotations: false,
+            spool: None,
+            rate_limits: RateLimits::default(),
+            dedup: None,
+            watch: WatchOptions::default(),
+            grace_period: SHUTDOWN_GRACE_PERIOD,
+            route: HIK8S_ROUTE_LOG.to_string(),
+            batch_size: BATCH_SIZE,
+            budget: ByteBudget::default(),
+            recv_timeout: RECV_TIMEOUT,
+        }
+    }
+}
+
+impl ReadOptions {
+    // Settings the config keeps as text are parsed here, errors name their key
+    pub fn from_config(config: &Config) -> Result<Self, ConfigError> {
+        let output_format = match &config.output_format {
+            Some(value) => value
+                .parse()
+                .map_err(invalid("output_format", ENV_OUTPUT_FORMAT))?,
+            None => OutputFormat::default(),
+        };
+
+        let multiline = match (&config.multiline, &config.multiline_start_pattern) {
+            (None, None) => None,
+            (presets, start_pattern) => {
+                let mut pattern =
+                    MultilinePattern::from_presets(presets.as_deref().unwrap_or_default())
+                        .map_err(invalid("multiline", ENV_MULTILINE))?;
+                if let Some(start_pattern) = start_pattern {
+                    pattern = pattern.with_start(start_pattern).map_err(invalid(
+                        "multiline_start_pattern",
+                        ENV_MULTILINE_START_PATTERN,
+                    ))?;
+                }
+                Some(pattern)
+            }
+        };
+        if multiline.is_some() && output_format == OutputFormat::Raw {
+            warn!("Multiline aggregation requires {ENV_OUTPUT_FORMAT}=structured, ignoring it");
+        }
+
+        // a rule is reported with the setting it is part of
+        LogFilter::new(&config.include, "").map_err(invalid("include", ENV_INCLUDE))?;
+        let filter = LogFilter::new(&config.include, &config.exclude)
+            .map_err(invalid("exclude", ENV_EXCLUDE))?;
+
+        let redactor = Redactor::new(&config.redact, config.redact_rules.clone())
+            .map_err(invalid("redact", ENV_REDACT))?;
+
+        let compression = match &config.compression {
+            Some(value) => value
+                .parse()
+                .map_err(invalid("compression", ENV_COMPRESSION))?,
+            None => Compression::default(),
+        };
+
+        let spool = config.spool_path.as_ref().map(|path| SpoolOptions {
+            path: path.clone(),
+            max_bytes: config.spool_max_bytes,
+            max_age: Duration::from_secs(config.spool_max_age_secs),
+        });
+
+        let rate_limits = RateLimits {
+            node: RateLimit {
+                bytes_per_sec: config.rate_limit_bytes,
+                lines_per_sec: config.rate_limit_lines,
+            },
+            container: RateLimit {
+                bytes_per_sec: config.container_rate_limit_bytes,
+                lines_per_sec: config.container_rate_limit_lines,
+            },
+        };
+
+        let dedup = match &config.dedup {
+            Some(value) => Some(DedupOptions {
+                mode: value.parse().map_err(invalid("dedup", ENV_DEDUP))?,
+                window: Duration::from_millis(config.dedup_window_ms),
+            }),
+            None => None,
+        };
+        if dedup.is_some() && output_format == OutputFormat::Raw {
+            warn!("Deduplication requires {ENV_OUTPUT_FORMAT}=structured, ignoring it");
+        }
+
+        let watch = WatchOptions {
+            mode: match &config.watch_mode {
+                Some(value) => value
+                    .parse()
+                    .map_err(invalid("watch_mode", ENV_WATCH_MODE))?,
+                None => WatchMode::default(),
+            },
+            poll_interval: Duration::from_millis(config.poll_interval_ms),
+            inotify_sleep: config.inotify_sleep(),
+        };
+
+        Ok(Self {
+            ship_compressed: config.ship_compressed_logs,
+            output_format,
+            multiline,
+            flush_timeout: Duration::from_millis(config.multiline_flush_timeout_ms),
+            filter,
+            redactor,
+            compression,
+            pod_enrichment: config.pod_enrichment,
+            pod_annotations: config.pod_annotations,
+            spool,
+            rate_limits,
+            dedup,
+            watch,
+            grace_period: Duration::from_secs(config.shutdown_grace_period_secs),
+            route: config.route.clone(),
+            batch_size: config.batch_size,
+            budget: ByteBudget::new(config.max_inflight_bytes),
+            recv_timeout: config.recv_timeout(),
+        })
+    }
+}
+
+fn invalid<E: Display>(key: &'static str, env: &'static str) -> impl FnOnce(E) -> ConfigError {
+    move |e| ConfigError::Invalid(key, env, e.to_string())
+}
@@ -1,107 +1,723 @@
-use shared::client::{create_form_data, Client};
-use std::io::Seek;
+use bytes::Bytes;
+use reqwest::StatusCode;
+use shared::client::{create_form_data, Client, Compression};
+use shared::health::{Heartbeat, READY_CAUGHT_UP};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::RecvTimeoutError;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio_stream::wrappers::UnboundedReceiverStream;
-
-use std::path::PathBuf;
-use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
-    sync::mpsc::Receiver,
-};
-use tracing::{debug, error, info};
+use std::time::Instant;
+use tokio_stream::wrappers::ReceiverStream;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::Metadata;
+use std::io::{self, BufRead};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::{fs::File, sync::mpsc::Receiver};
+use tracing::{debug, error, info, warn};
 
-use crate::constant::HIK8S_ROUTE_LOG;
+use crate::checkpoint::{Checkpoint, CheckpointStore};
+use crate::metrics::{
+    BYTES_READ, BYTES_UPLOADED, CHANNEL_DEPTH, FILE_LAG, REJECTED_CHUNKS, UPLOAD_DURATION,
+    UPLOAD_FAILURES,
+};
+use crate::pods::{PodCache, PodSettings};
+use crate::spool::Spool;
 
 use super::error::ReadThreadError;
-use super::reader::{get_reader, read_chunk};
+use super::metadata::UploadMetadata;
+use super::options::ReadOptions;
+use super::reader::{get_gz_reader, get_reader, read_chunk, Pipeline, RateLimiter};
+use super::retry::RetryQueue;
+use super::rotation::{find_compressed_rotation, find_rotated_file, resume_position, Resume};
+use super::source::{is_log_file, PodLogSource};
 
 pub async fn read_file_and_send_data<C: Client>(
     event_receiver: Receiver<HashSet<PathBuf>>,
     client: C,
+    checkpoints: CheckpointStore,
+    options: ReadOptions,
+    pods: PodCache,
+    heartbeat: Heartbeat,
     termination_signal: Arc<AtomicBool>,
 ) -> Result<(), ReadThreadError> {
     info!("Starting read_file_and_send_data thread...");
-    let mut positions: HashMap<PathBuf, u64> = HashMap::new();
+    let spool = options.spool.clone().map(Spool::open).transpose()?;
+    let rate_limiter = RateLimiter::new(options.rate_limits);
+
+    // Pods deleted while logd was not running
+    checkpoints.retain(|path| path.parent().is_some_and(Path::exists));
+    let mut sender = FileSender {
+        client,
+        checkpoints,
+        options,
+        pods,
+        retries: RetryQueue::default(),
+        held_back: HashMap::new(),
+        unlisted_events: HashSet::new(),
+        spool,
+        replays: RetryQueue::default(),
+        rate_limiter,
+        open_files: HashMap::new(),
+        shutting_down: false,
+        heartbeat,
+    };
     loop {
+        sender.heartbeat.beat();
         if termination_signal.load(Ordering::SeqCst) {
             break;
         }
-        match event_receiver.recv_timeout(Duration::from_millis(500)) {
+        sender
+            .checkpoints
+            .flush_if_due()
+            .map_err(ReadThreadError::Checkpoint)
+            .inspect_err(|e| error!("{e}"))
+            .ok();
+
+        // Upload spooled chunks before new data
+        sender.replay_spool().await;
+
+        // Retry failed uploads from the last committed offset
+        for path in sender.retries.due() {
+            sender.read_and_send_file(&path).await;
+        }
+
+        // Ship records that waited too long for the record after them
+        for path in sender.flush_due() {
+            sender.read_and_send_file(&path).await;
+        }
+
+        if sender.pods.is_listed() {
+            for path in std::mem::take(&mut sender.unlisted_events) {
+                sender.read_and_send_file(&path).await;
+            }
+        }
+
+        match event_receiver.recv_timeout(sender.options.recv_timeout) {
             Ok(paths) => {
+                CHANNEL_DEPTH.with_label_values(&["file_events"]).dec();
+                // the settings of every pod are unknown until the first list
+                if !sender.pods.is_listed() {
+                    sender.unlisted_events.extend(paths);
+                    continue;
+                }
                 for path in paths {
-                    // Read file
-                    if path.extension().and_then(|ext| ext.to_str()) == Some("gz") {
-                        info!("Skipping .gz file: {}", path.display());
+                    if sender.retries.is_waiting(&path) {
                         continue;
-                    } else {
-                        debug!("Reading file: {}", path.display());
                     }
-
-                    let file = match File::open(&path) {
-                        Ok(file) => file,
-                        Err(e) => {
-                            tracing::error!("Failed to open file {}: {}", path.display(), e);
-                            continue;
-                        }
-                    };
-
-                    // Get file position
-                    let position = positions.get(&path).unwrap_or(&0);
-
-                    // Get reader at position
-                    let mut reader = get_reader(file, *position).expect("Failed to get reader");
-
-                    // Read new entries
-                    let (data_sender, data_receiver) = tokio::sync::mpsc::unbounded_channel();
-                    read_chunk(&mut reader, 1048576, data_sender)
-                        .map_err(ReadThreadError::Reader)
-                        .inspect_err(|e| error!("Path {}: {}", path.display(), e))
-                        .ok();
-
-                    // Update file position
-                    let new_position = reader.stream_position().unwrap();
-                    positions.insert(path.clone(), new_position);
-
-                    let parent_path = path.parent().unwrap().to_str().unwrap();
-                    let file_name = path.file_name().unwrap().to_str().unwrap();
-
-                    let metadata = serde_json::json!({
-                        "path": parent_path,
-                        "file": file_name
-                    });
-
-                    // Receiver stream
-                    let stream = UnboundedReceiverStream::new(data_receiver);
-
-                    // Form data
-                    let form_data = match create_form_data(metadata, stream) {
-                        Ok(form_data) => form_data,
-                        Err(e) => {
-                            error!("Skipping data: {}", ReadThreadError::FormData(e));
-                            continue;
-                        }
-                    };
-
-                    // Stream data
-                    client
-                        .send_multipart_request(HIK8S_ROUTE_LOG, form_data)
-                        .await
-                        .map_err(ReadThreadError::Hik8sClient)
-                        .inspect_err(|e| error!("{e}"))
-                        .ok();
+                    sender.read_and_send_file(&path).await;
                 }
             }
+            // no file changed, ready even if a restart found nothing to upload
             Err(RecvTimeoutError::Timeout) => {
+                if sender.is_caught_up() {
+                    sender.heartbeat.mark(READY_CAUGHT_UP);
+                }
                 continue;
             }
+            // the file events thread stopped first
+            Err(RecvTimeoutError::Disconnected) if termination_signal.load(Ordering::SeqCst) => {
+                break;
+            }
             Err(e @ RecvTimeoutError::Disconnected) => {
+                sender.checkpoints.flush()?;
                 return Err(e.into());
             }
         }
     }
+    let queued = event_receiver
+        .try_iter()
+        .inspect(|_| CHANNEL_DEPTH.with_label_values(&["file_events"]).dec())
+        .flatten()
+        .collect();
+    // unread files are picked up from their checkpoints after the restart
+    if sender.pods.is_listed() {
+        sender.drain(queued).await;
+    }
+    sender.checkpoints.flush()?;
     Ok(())
 }
+
+struct FileSender<C: Client> {
+    client: C,
+    checkpoints: CheckpointStore,
+    options: ReadOptions,
+    pods: PodCache,
+    retries: RetryQueue,
+    // files whose last record is held back at EOF, until when
+    held_back: HashMap<PathBuf, Instant>,
+    // files changed before the pod watcher listed the pods, read once it has
+    unlisted_events: HashSet<PathBuf>,
+    // chunks that failed to upload, replayed with backoff keyed by the spool path
+    spool: Option<Spool>,
+    replays: RetryQueue,
+    rate_limiter: RateLimiter,
+    // files that were read, kept open to drain them once they are deleted
+    open_files: HashMap<PathBuf, File>,
+    // records held back at EOF are shipped, no lines follow them before the exit
+    shutting_down: bool,
+    // beats once per loop and per file, a wedged upload stops it
+    heartbeat: Heartbeat,
+}
+
+impl<C: Client> FileSender<C> {
+    fn flush_due(&self) -> Vec<PathBuf> {
+        self.held_back
+            .iter()
+            .filter(|(path, deadline)| {
+                Instant::now() >= **deadline && !self.retries.is_waiting(path)
+            })
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    // Reads the files with queued events and ships the records held back at EOF until the
+    // grace period ends. Files that were not drained resume from their checkpoints.
+    async fn drain(&mut self, mut paths: HashSet<PathBuf>) {
+        paths.extend(self.held_back.keys().cloned());
+        if paths.is_empty() {
+            return;
+        }
+        info!("Draining {} files before shutdown", paths.len());
+        self.shutting_down = true;
+        let grace_period = self.options.grace_period;
+        let drain = async {
+            for path in paths {
+                self.read_and_send_file(&path).await;
+            }
+        };
+        if tokio::time::timeout(grace_period, drain).await.is_err() {
+            warn!("Shutdown grace period of {grace_period:?} ended before all files were drained");
+        }
+    }
+
+    // Uploads spooled chunks oldest first until the spool is empty or an upload fails
+    // No failed upload or spooled chunk waits
+    fn is_caught_up(&self) -> bool {
+        self.retries.is_empty()
+            && self.unlisted_events.is_empty()
+            && self.spool.as_ref().is_none_or(|spool| spool.is_empty())
+    }
+
+    async fn replay_spool(&mut self) {
+        let Some(spool) = self.spool.as_mut() else {
+            return;
+        };
+        spool.evict();
+        let spool_path = spool.path().to_path_buf();
+        if spool.is_empty() || self.replays.is_waiting(&spool_path) {
+            return;
+        }
+        info!(
+            "Replaying {} spooled chunks ({} bytes)",
+            spool.len(),
+            spool.bytes()
+        );
+        loop {
+            let Some(spool) = self.spool.as_mut() else {
+                return;
+            };
+            let (metadata, data) = match spool.front() {
+                None => {
+                    self.replays.remove(&spool_path);
+                    return;
+                }
+                Some(Ok(segment)) => segment,
+                Some(Err(e)) => {
+                    error!("Dropping unreadable spooled chunk: {e}");
+                    spool.pop_front().inspect_err(|e| error!("{e}")).ok();
+                    continue;
+                }
+            };
+            let _permit = self.options.budget.acquire(data.len()).await;
+            match self.upload(metadata, data).await {
+                Ok(()) => {}
+                Err(e @ ReadThreadError::Rejected(_)) => {
+                    error!("Dropping spooled chunk: {e}");
+                }
+                Err(e) => {
+                    warn!("Replay of spooled chunk failed: {e}");
+                    self.replays.schedule(&spool_path);
+                    return;
+                }
+            }
+            if let Some(spool) = self.spool.as_mut() {
+                spool.pop_front().inspect_err(|e| error!("{e}")).ok();
+            }
+        }
+    }
+
+    // The codec may have changed since the metadata was created (spooled chunks, 415 fallback)
+    async fn upload(
+        &mut self,
+        mut metadata: serde_json::Value,
+        data: Bytes,
+    ) -> Result<(), ReadThreadError> {
+        if let Some(metadata) = metadata.as_object_mut() {
+            match self.options.compression.content_encoding() {
+                Some(encoding) => metadata.insert("encoding".to_string(), encoding.into()),
+                None => metadata.remove("encoding"),
+            };
+        }
+        let data_len = data.len();
+        let (data_sender, data_receiver) = tokio::sync::mpsc::channel(1);
+        data_sender.try_send(Ok(data)).ok();
+        let stream = ReceiverStream::new(data_receiver);
+        let form_data = create_form_data(metadata, stream, self.options.compression)?;
+
+        let start = Instant::now();
+        let result = self
+            .client
+            .send_multipart_request(&self.options.route, form_data)
+            .await;
+        let outcome = if result.is_ok() { "success" } else { "failure" };
+        UPLOAD_DURATION
+            .with_label_values(&[outcome])
+            .observe(start.elapsed().as_secs_f64());
+        match &result {
+            Ok(()) => BYTES_UPLOADED.inc_by(data_len as u64),
+            Err(e) => {
+                let status = e
+                    .status()
+                    .map_or("none".to_string(), |s| s.as_u16().to_string());
+                UPLOAD_FAILURES.with_label_values(&[&status]).inc();
+            }
+        }
+        match result {
+            Err(e)
+                if e.status() == Some(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+                    && self.options.compression != Compression::None =>
+            {
+                // The server does not accept the codec, fall back to plain uploads
+                warn!(
+                    "Server rejected {} compressed uploads, disabling compression",
+                    self.options.compression
+                );
+                self.options.compression = Compression::None;
+                Err(e.into())
+            }
+            // Retrying would block the file or the spool, the chunk is dropped
+            Err(e) if e.is_permanent() => {
+                let status = e.status().map_or(0, |s| s.as_u16()).to_string();
+                REJECTED_CHUNKS.with_label_values(&[&status]).inc();
+                Err(ReadThreadError::Rejected(e))
+            }
+            result => Ok(result?),
+        }
+    }
+
+    // Appends a chunk to the spool. Returns false if the spool is disabled or not writable.
+    fn spool_chunk(&mut self, metadata: &serde_json::Value, data: &[u8]) -> bool {
+        let Some(spool) = self.spool.as_mut() else {
+            return false;
+        };
+        spool
+            .push(metadata, data)
+            .map_err(ReadThreadError::Spool)
+            .inspect_err(|e| error!("{e}"))
+            .is_ok()
+    }
+
+    async fn read_and_send_file(&mut self, path: &Path) {
+        self.heartbeat.beat();
+        self.try_read_and_send_file(path)
+            .await
+            .inspect_err(|e| error!("Path {}: {}", path.display(), e))
+            .ok();
+    }
+
+    async fn try_read_and_send_file(&mut self, path: &Path) -> Result<(), ReadThreadError> {
+        if !is_log_file(path) {
+            debug!("Skipping file that is not a log: {}", path.display());
+            return Ok(());
+        }
+        if !self.options.filter.allows_path(path) {
+            debug!("Skipping filtered file: {}", path.display());
+            return Ok(());
+        }
+
+        if !path.exists() {
+            return self.forget_deleted_file(path).await;
+        }
+
+        // Read file
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("tmp") => {
+                debug!("Skipping temporary file: {}", path.display());
+                return Ok(());
+            }
+            Some("gz") if !self.options.ship_compressed => {
+                info!("Skipping .gz file: {}", path.display());
+                return Ok(());
+            }
+            Some("gz") => {
+                self.send_compressed_file(path).await?;
+                return Ok(());
+            }
+            _ => debug!("Reading file: {}", path.display()),
+        }
+
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+
+        // Get file position
+        let position = match resume_position(path, &metadata, &self.checkpoints) {
+            Resume::At(position) => position,
+            Resume::Rotated(checkpoint) => {
+                // Finish draining the previous inode before switching to the new file
+                let previous = self.open_files.remove(path).filter(|file| {
+                    file.metadata()
+                        .is_ok_and(|metadata| checkpoint.matches(&metadata))
+                });
+                if !self.drain_rotated_file(path, &checkpoint, previous).await? {
+                    return Ok(());
+                }
+                0
+            }
+        };
+        self.open_files
+            .insert(path.to_path_buf(), file.try_clone()?);
+
+        // Get reader at position
+        let mut reader = get_reader(file, position)?;
+        self.send_file(path, path, &mut reader, &metadata, position)
+            .await?;
+        Ok(())
+    }
+
+    // Ships the decompressed content of a rotated .gz file that was not fully shipped yet.
+    // Returns false if an upload failed and the file was scheduled for retry.
+    async fn send_compressed_file(&mut self, path: &Path) -> Result<bool, ReadThreadError> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+
+        // Offsets are positions in the decompressed data, which continues the file before
+        // compression (0.log.<timestamp>.gz -> 0.log.<timestamp>)
+        let uncompressed_path = path.with_extension("");
+        let position = match self.checkpoints.get(path) {
+            Some(checkpoint) if checkpoint.matches(&metadata) => checkpoint.offset,
+            _ => self
+                .checkpoints
+                .get(&uncompressed_path)
+                .map_or(0, |checkpoint| checkpoint.offset),
+        };
+        debug!(
+            "Reading compressed file: {} from offset {}",
+            path.display(),
+            position
+        );
+
+        let mut reader = get_gz_reader(file, position)?;
+        if !self
+            .send_file(path, path, &mut reader, &metadata, position)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        // The checkpoint of the .gz file marks it as shipped
+        let offset = self
+            .checkpoints
+            .get(path)
+            .filter(|checkpoint| checkpoint.matches(&metadata))
+            .map_or(position, |checkpoint| checkpoint.offset);
+        self.checkpoints.commit(path, &metadata, offset);
+        if !uncompressed_path.exists() {
+            self.checkpoints.remove(&uncompressed_path);
+        }
+        Ok(true)
+    }
+
+    // Ships the unread tail of a deleted file from its open handle and forgets the file
+    async fn forget_deleted_file(&mut self, path: &Path) -> Result<(), ReadThreadError> {
+        if let Some(file) = self.open_files.remove(path) {
+            let metadata = file.metadata()?;
+            let checkpoint = self.checkpoints.get(path);
+            let position = checkpoint
+                .filter(|checkpoint| checkpoint.matches(&metadata))
+                .map_or(0, |checkpoint| checkpoint.offset);
+            if position < metadata.len() {
+                info!(
+                    "{} was deleted, draining from offset {}",
+                    path.display(),
+                    position
+                );
+                let mut reader = get_reader(file, position)?;
+                if !self
+                    .send_file(path, path, &mut reader, &metadata, position)
+                    .await?
+                {
+                    // keep the handle for the retry
+                    self.open_files
+                        .insert(path.to_path_buf(), reader.into_inner());
+                    return Ok(());
+                }
+            }
+        }
+
+        // A rotation compressed by kubelet resumes from the checkpoint of the deleted file
+        let compressed_path = PathBuf::from(format!("{}.gz", path.display()));
+        if !(self.options.ship_compressed && compressed_path.exists()) {
+            self.checkpoints.remove(path);
+        }
+        self.retries.remove(path);
+        self.held_back.remove(path);
+        FILE_LAG
+            .remove_label_values(&[&path.to_string_lossy()])
+            .ok();
+        debug!("Forgot deleted file {}", path.display());
+        Ok(())
+    }
+
+    // Returns false if the previous file still has data that failed to upload
+    async fn drain_rotated_file(
+        &mut self,
+        path: &Path,
+        previous: &Checkpoint,
+        previous_file: Option<File>,
+    ) -> Result<bool, ReadThreadError> {
+        let Some(rotated_path) = find_rotated_file(path, previous) else {
+            // The previous file may have been compressed while logd was not running
+            if self.options.ship_compressed {
+                if let Some(compressed_path) = find_compressed_rotation(path, &self.checkpoints) {
+                    info!(
+                        "{} was rotated to {}, draining from offset {}",
+                        path.display(),
+                        compressed_path.display(),
+                        previous.offset
+                    );
+                    let metadata = std::fs::metadata(&compressed_path)?;
+                    self.checkpoints
+                        .commit(&compressed_path, &metadata, previous.offset);
+                    self.checkpoints.remove(path);
+                    return self.send_compressed_file(&compressed_path).await;
+                }
+            }
+            // The previous file was deleted after logd read it, drain it from its handle
+            if let Some(file) = previous_file {
+                let metadata = file.metadata()?;
+                info!(
+                    "{} was replaced, draining the previous file from offset {}",
+                    path.display(),
+                    previous.offset
+                );
+                let mut reader = get_reader(file, previous.offset)?;
+                if !self
+                    .send_file(path, path, &mut reader, &metadata, previous.offset)
+                    .await?
+                {
+                    self.open_files
+                        .insert(path.to_path_buf(), reader.into_inner());
+                    return Ok(false);
+                }
+                self.checkpoints.remove(path);
+                return Ok(true);
+            }
+            warn!(
+                "{} was rotated and the previous file is gone, data after offset {} is lost",
+                path.display(),
+                previous.offset
+            );
+            self.checkpoints.remove(path);
+            return Ok(true);
+        };
+        info!(
+            "{} was rotated to {}, draining from offset {}",
+            path.display(),
+            rotated_path.display(),
+            previous.offset
+        );
+
+        // Progress is committed under the original path until the file is drained
+        let file = File::open(&rotated_path)?;
+        let metadata = file.metadata()?;
+        let mut reader = get_reader(file, previous.offset)?;
+        if !self
+            .send_file(&rotated_path, path, &mut reader, &metadata, previous.offset)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        // The rotated file keeps its offset in case it receives more data
+        let offset = self
+            .checkpoints
+            .get(path)
+            .map_or(previous.offset, |c| c.offset);
+        self.checkpoints.remove(path);
+        self.checkpoints.commit(&rotated_path, &metadata, offset);
+        Ok(true)
+    }
+
+    // Uploads the reader from position to EOF, committing each chunk under checkpoint_path.
+    // Returns false if an upload failed and checkpoint_path was scheduled for retry.
+    async fn send_file(
+        &mut self,
+        path: &Path,
+        checkpoint_path: &Path,
+        reader: &mut (impl BufRead + Send),
+        metadata: &Metadata,
+        mut position: u64,
+    ) -> Result<bool, ReadThreadError> {
+        let parent_path = path.parent().unwrap().to_str().unwrap();
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+
+        let source = PodLogSource::from_path(path);
+        let pod = source
+            .as_ref()
+            .and_then(|source| self.pods.get(&source.pod_uid));
+        let settings = match &pod {
+            Some(pod) if self.options.pod_annotations => pod.settings.clone(),
+            _ => PodSettings::default(),
+        };
+        if settings.logs_disabled {
+            // Lines written while logs are disabled are never shipped
+            let skipped = io::copy(reader, &mut io::sink())?;
+            debug!(
+                "Logs of {} are disabled by annotation, skipping {} bytes",
+                path.display(),
+                skipped
+            );
+            self.checkpoints
+                .commit(checkpoint_path, metadata, position + skipped);
+            return Ok(true);
+        }
+        let pod = pod.filter(|_| self.options.pod_enrichment);
+        // Rate limits apply to all files of a container, including rotated ones
+        let container = source.as_ref().map_or_else(
+            || parent_path.to_string(),
+            |source| {
+                format!(
+                    "{}/{}/{}",
+                    source.namespace, source.pod_name, source.container_name
+                )
+            },
+        );
+        let form_metadata = serde_json::to_value(UploadMetadata {
+            path: parent_path.to_string(),
+            file: file_name.to_string(),
+            format: self.options.output_format.to_string(),
+            encoding: self.options.compression.content_encoding(),
+            source,
+            pod,
+        })?;
+
+        // Rotated files (drained, compressed or deleted) receive no more lines to complete a
+        // record
+        let replaced = std::fs::metadata(path).map_or(true, |current| {
+            current.dev() != metadata.dev() || current.ino() != metadata.ino()
+        });
+        let rotated =
+            replaced || path != checkpoint_path || path.extension().is_some_and(|ext| ext == "gz");
+        let flush = rotated
+            || self.shutting_down
+            || self
+                .held_back
+                .get(checkpoint_path)
+                .is_some_and(|deadline| Instant::now() >= *deadline);
+        let multiline = settings
+            .multiline
+            .or_else(|| self.options.multiline.clone());
+        let mut pipeline = Pipeline::new(self.options.output_format, multiline)
+            .with_dedup(self.options.dedup)
+            .with_flush(flush)
+            .with_sample_rate(settings.sample_rate)
+            .with_redactor(self.options.redactor.clone())
+            .with_rate_limiter(self.rate_limiter.clone(), &container);
+
+        loop {
+            // Wait for the budget before reading, it is returned once the chunk is delivered.
+            // A budget smaller than the batch size limits the chunks.
+            let budget = self.options.budget.clone();
+            let batch_size = self.options.batch_size.min(budget.capacity());
+            if budget.available() < batch_size {
+                debug!("Byte budget used up, pausing reads of {}", path.display());
+            }
+            let _permit = budget.acquire(batch_size).await;
+
+            // Read new entries
+            let (data_sender, mut data_receiver) = tokio::sync::mpsc::channel(1);
+            let chunk = read_chunk(reader, batch_size, &mut pipeline, data_sender)?;
+            BYTES_READ.inc_by(chunk.bytes_read as u64);
+            if chunk.bytes_read == 0 && chunk.bytes_sent == 0 {
+                self.track_held_back(checkpoint_path, &pipeline);
+                return Ok(true);
+            }
+
+            let data = match data_receiver.try_recv() {
+                Ok(Ok(data)) => data,
+                _ => Bytes::new(),
+            };
+
+            // The offset is committed once the server acknowledged the chunk, it was spooled or
+            // the server rejected it for good. New chunks are uploaded while older chunks wait
+            // in the spool, so a spooled chunk that keeps failing does not hold them back.
+            let delivered = match chunk.bytes_sent {
+                0 => true,
+                _ => match self.upload(form_metadata.clone(), data.clone()).await {
+                    Ok(()) => true,
+                    Err(e @ ReadThreadError::Rejected(_)) => {
+                        error!(
+                            "Dropping chunk of {} at offset {}: {}",
+                            path.display(),
+                            position,
+                            e
+                        );
+                        true
+                    }
+                    Err(e) if self.spool.is_some() => {
+                        warn!(
+                            "Upload of {} failed, spooling from offset {}: {}",
+                            path.display(),
+                            position,
+                            e
+                        );
+                        self.spool_chunk(&form_metadata, &data)
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Upload of {} failed, retrying from offset {}: {}",
+                            path.display(),
+                            position,
+                            e
+                        );
+                        false
+                    }
+                },
+            };
+            if !delivered {
+                self.retries.schedule(checkpoint_path);
+                return Ok(false);
+            }
+            position += chunk.bytes_read as u64;
+            self.checkpoints.commit(checkpoint_path, metadata, position);
+            pipeline.commit();
+            // offsets of compressed files count the decompressed bytes
+            if path == checkpoint_path {
+                FILE_LAG
+                    .with_label_values(&[&path.to_string_lossy()])
+                    .set(metadata.len().saturating_sub(position) as i64);
+            }
+            self.retries.remove(checkpoint_path);
+            if chunk.eof {
+                self.track_held_back(checkpoint_path, &pipeline);
+                return Ok(true);
+            }
+        }
+    }
+
+    fn track_held_back(&mut self, path: &Path, pipeline: &Pipeline) {
+        if pipeline.holds_back() {
+            // a read that emitted records holds back a newer record
+            let timeout = pipeline.hold_timeout(self.options.flush_timeout);
+            if pipeline.clean_offset() > 0 {
+                self.held_back.remove(path);
+            }
+            self.held_back
+                .entry(path.to_path_buf())
+                .or_insert_with(|| Instant::now() + timeout);
+        } else {
+            self.held_back.remove(path);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use std::borrow::Cow;
+
+use super::record::LogLine;
+
+// <RFC3339Nano> <stdout|stderr> <P|F> <message>
+pub fn parse_cri_line(line: &str) -> Option<LogLine<'_>> {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    let mut parts = line.splitn(4, ' ');
+    let timestamp = parts.next().filter(|ts| ts.contains('T'))?;
+    let stream = parts.next()?.parse().ok()?;
+    let partial = match parts.next()? {
+        "P" => true,
+        "F" => false,
+        _ => return None,
+    };
+    // the message of an empty line is missing
+    let message = parts.next().unwrap_or("");
+    Some(LogLine {
+        timestamp: Cow::Borrowed(timestamp),
+        stream,
+        partial,
+        message: Cow::Borrowed(message),
+    })
+}
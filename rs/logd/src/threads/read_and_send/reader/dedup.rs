@@ -0,0 +1,123 @@
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use super::record::LogRecord;
+
+// Numbers, hex ids and uuids that differ between otherwise identical lines
+static VARIABLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b[0-9a-fA-F]{8,}(?:-[0-9a-fA-F]{4,})*\b|\d+").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DedupMode {
+    // messages are equal
+    Exact,
+    // messages are equal apart from numbers and ids
+    Similar,
+}
+
+impl FromStr for DedupMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "exact" => Ok(Self::Exact),
+            "similar" => Ok(Self::Similar),
+            other => Err(format!("unknown dedup mode: {other}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DedupOptions {
+    pub mode: DedupMode,
+    // repeats are collapsed until this long after the first record
+    pub window: Duration,
+}
+
+struct Pending {
+    record: LogRecord,
+    key: String,
+    start: u64,
+    first: Option<DateTime<FixedOffset>>,
+    count: u64,
+    last_timestamp: Option<String>,
+}
+
+// Collapses consecutive repeats of a record into the first one with a repeat count. The
+// window is measured with the record timestamps, records without one are not collapsed.
+pub struct Deduplicator {
+    options: DedupOptions,
+    pending: Option<Pending>,
+}
+
+impl Deduplicator {
+    pub fn new(options: DedupOptions) -> Self {
+        Self {
+            options,
+            pending: None,
+        }
+    }
+
+    // Returns the previous record once record does not repeat it
+    pub fn push(&mut self, record: LogRecord, start: u64) -> Option<LogRecord> {
+        let timestamp = record
+            .timestamp
+            .as_deref()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok());
+        let key = match self.options.mode {
+            DedupMode::Exact => Cow::Borrowed(record.message.as_str()),
+            DedupMode::Similar => VARIABLE.replace_all(&record.message, "#"),
+        };
+        if let Some(pending) = &mut self.pending {
+            let in_window = match (pending.first, timestamp) {
+                (Some(first), Some(timestamp)) => (timestamp - first)
+                    .to_std()
+                    .is_ok_and(|elapsed| elapsed <= self.options.window),
+                _ => false,
+            };
+            if in_window && pending.record.stream == record.stream && pending.key == key {
+                pending.count += 1;
+                pending.last_timestamp = record.timestamp;
+                return None;
+            }
+        }
+        let key = key.into_owned();
+        self.pending
+            .replace(Pending {
+                record,
+                key,
+                start,
+                first: timestamp,
+                count: 1,
+                last_timestamp: None,
+            })
+            .map(Pending::into_record)
+    }
+
+    pub fn window(&self) -> Duration {
+        self.options.window
+    }
+
+    pub fn pending_start(&self) -> Option<u64> {
+        self.pending.as_ref().map(|pending| pending.start)
+    }
+
+    pub fn flush(&mut self) -> Option<LogRecord> {
+        self.pending.take().map(Pending::into_record)
+    }
+}
+
+impl Pending {
+    fn into_record(self) -> LogRecord {
+        let mut record = self.record;
+        if self.count > 1 {
+            record.repeat_count = Some(self.count);
+            record.last_timestamp = self.last_timestamp;
+        }
+        record
+    }
+}
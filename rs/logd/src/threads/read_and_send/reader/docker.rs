@@ -0,0 +1,31 @@
+use serde::Deserialize;
+use std::borrow::Cow;
+
+use super::record::{LogLine, LogStream};
+
+#[derive(Deserialize)]
+struct DockerJsonLine {
+    log: String,
+    stream: LogStream,
+    time: String,
+}
+
+// {"log":"<message>\n","stream":"<stdout|stderr>","time":"<RFC3339Nano>"}
+pub fn parse_docker_line(line: &str) -> Option<LogLine<'static>> {
+    if !line.starts_with('{') {
+        return None;
+    }
+    let docker: DockerJsonLine = serde_json::from_str(line).ok()?;
+
+    // docker splits long lines, only the last part ends with a newline
+    let (message, partial) = match docker.log.strip_suffix('\n') {
+        Some(message) => (message.to_string(), false),
+        None => (docker.log, true),
+    };
+    Some(LogLine {
+        timestamp: Cow::Owned(docker.time),
+        stream: docker.stream,
+        partial,
+        message: Cow::Owned(message),
+    })
+}
@@ -1,13 +1,15 @@
 use std::sync::mpsc::SendError;
 use thiserror::Error;
-use tokio::sync::mpsc::error::SendError as TokioSendError;
+use tokio::sync::mpsc::error::TrySendError;
 
 #[derive(Error, Debug)]
 pub enum ReaderError {
     #[error("I/O error")]
     Io(#[from] std::io::Error),
     #[error("Send error")]
-    SendBytes(#[from] TokioSendError<Result<bytes::Bytes, hyper::Error>>),
+    SendBytes(#[from] TrySendError<Result<bytes::Bytes, hyper::Error>>),
     #[error("Send error")]
     SendString(#[from] SendError<String>),
+    #[error("Json serialize error: {0}")]
+    Json(#[from] serde_json::Error),
 }
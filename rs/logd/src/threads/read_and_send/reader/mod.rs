@@ -1,6 +1,21 @@
+mod cri;
+mod dedup;
+mod docker;
 mod error;
+mod multiline;
+mod parser;
+mod pipeline;
+mod rate_limit;
 mod reader;
+mod record;
+mod redact;
 mod test;
 
+pub use dedup::DedupOptions;
 pub use error::ReaderError;
-pub use reader::{get_reader, read_chunk};
+pub use multiline::MultilinePattern;
+pub use pipeline::Pipeline;
+pub use rate_limit::{RateLimit, RateLimiter, RateLimits};
+pub use reader::{get_gz_reader, get_reader, read_chunk};
+pub use record::OutputFormat;
+pub use redact::{RedactionRuleConfig, Redactor};
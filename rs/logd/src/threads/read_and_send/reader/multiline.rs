@@ -0,0 +1,105 @@
+use regex::Regex;
+
+use crate::constant::MULTILINE_MAX_LINES;
+
+use super::record::LogRecord;
+
+// Continuation lines of the stack traces of common runtimes
+const JAVA_CONTINUATION: &str = r"^\s+at\s|^\s+\.\.\. \d+ (more|common frames omitted)|^\s*Caused by: |^\s+Suppressed: |^[\w$]+(\.[\w$]+)+(Exception|Error|Throwable)(: |$)";
+const PYTHON_CONTINUATION: &str = r"^\s|^$|^Traceback \(most recent call last\):|^[\w.]+(Error|Exception|Warning|Exit|Interrupt)(: |$)|^During handling of the above exception|^The above exception was the direct cause";
+const GO_CONTINUATION: &str =
+    r"^\s|^$|^goroutine \d+ \[|^created by |^exit status \d+$|^\[signal |^[\w./*()-]+\(.*\)$";
+
+// Decides which records continue the previous record of the same stream
+#[derive(Clone, Debug, Default)]
+pub struct MultilinePattern {
+    // a record that does not match starts no new record
+    start: Option<Regex>,
+    // a record that matches any of these continues the previous record
+    continuations: Vec<Regex>,
+}
+
+impl MultilinePattern {
+    // Builds a pattern from a comma separated list of presets: java, python, go
+    pub fn from_presets(presets: &str) -> Result<Self, String> {
+        let continuations = presets
+            .split(',')
+            .map(str::trim)
+            .filter(|preset| !preset.is_empty())
+            .map(|preset| match preset.to_lowercase().as_str() {
+                "java" => Ok(JAVA_CONTINUATION),
+                "python" => Ok(PYTHON_CONTINUATION),
+                "go" => Ok(GO_CONTINUATION),
+                other => Err(format!("unknown multiline preset: {other}")),
+            })
+            .map(|pattern| pattern.map(|pattern| Regex::new(pattern).unwrap()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            start: None,
+            continuations,
+        })
+    }
+
+    pub fn with_start(mut self, start: &str) -> Result<Self, String> {
+        self.start = Some(Regex::new(start).map_err(|e| e.to_string())?);
+        Ok(self)
+    }
+
+    pub fn is_continuation(&self, message: &str) -> bool {
+        self.continuations.iter().any(|c| c.is_match(message))
+            || self.start.as_ref().is_some_and(|s| !s.is_match(message))
+    }
+}
+
+struct Pending {
+    record: LogRecord,
+    // input offset of the first line of the record
+    start: u64,
+    lines: usize,
+}
+
+// Joins records that continue the previous record, e.g. the lines of a stack trace.
+// The last record is held back until a record that starts a new one arrives.
+pub struct MultilineAggregator {
+    pattern: MultilinePattern,
+    pending: Option<Pending>,
+}
+
+impl MultilineAggregator {
+    pub fn new(pattern: MultilinePattern) -> Self {
+        Self {
+            pattern,
+            pending: None,
+        }
+    }
+
+    // Returns the previous record once record starts a new one
+    pub fn push(&mut self, record: LogRecord, start: u64) -> Option<LogRecord> {
+        if let Some(pending) = &mut self.pending {
+            if pending.record.stream == record.stream
+                && pending.lines < MULTILINE_MAX_LINES
+                && self.pattern.is_continuation(&record.message)
+            {
+                pending.record.message.push('\n');
+                pending.record.message.push_str(&record.message);
+                pending.lines += 1;
+                return None;
+            }
+        }
+        self.pending
+            .replace(Pending {
+                record,
+                start,
+                lines: 1,
+            })
+            .map(|pending| pending.record)
+    }
+
+    pub fn pending_start(&self) -> Option<u64> {
+        self.pending.as_ref().map(|pending| pending.start)
+    }
+
+    pub fn flush(&mut self) -> Option<LogRecord> {
+        self.pending.take().map(|pending| pending.record)
+    }
+}
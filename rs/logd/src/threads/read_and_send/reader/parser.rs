@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use super::cri::parse_cri_line;
+use super::docker::parse_docker_line;
+use super::record::{LogLine, LogRecord, LogStream};
+
+// Detects the log format of a line: docker json-file or CRI
+pub fn parse_line(line: &str) -> Option<LogLine<'_>> {
+    if line.starts_with('{') {
+        parse_docker_line(line)
+    } else {
+        parse_cri_line(line)
+    }
+}
+
+// Joins partial lines of each stream into full records
+#[derive(Default)]
+pub struct RecordParser {
+    // partial record of each stream with the input offset of its first fragment
+    partials: HashMap<LogStream, (LogRecord, u64)>,
+}
+
+impl RecordParser {
+    // Returns a complete record with the input offset of its first line
+    pub fn push(&mut self, line: &str, offset: u64) -> Option<(LogRecord, u64)> {
+        let Some(parsed) = parse_line(line) else {
+            return Some((
+                LogRecord::plain(line.strip_suffix('\n').unwrap_or(line)),
+                offset,
+            ));
+        };
+
+        // the record keeps the timestamp of its first fragment
+        let (record, _) = self.partials.entry(parsed.stream).or_insert_with(|| {
+            let record = LogRecord {
+                timestamp: Some(parsed.timestamp.to_string()),
+                stream: Some(parsed.stream),
+                message: String::new(),
+                repeat_count: None,
+                last_timestamp: None,
+            };
+            (record, offset)
+        });
+        record.message.push_str(&parsed.message);
+
+        if parsed.partial {
+            return None;
+        }
+        self.partials.remove(&parsed.stream)
+    }
+
+    pub fn pending_start(&self) -> Option<u64> {
+        self.partials.values().map(|(_, start)| *start).min()
+    }
+
+    // Partial records in the order they started
+    pub fn flush(&mut self) -> Vec<LogRecord> {
+        let mut partials: Vec<_> = self.partials.drain().map(|(_, partial)| partial).collect();
+        partials.sort_by_key(|(_, start)| *start);
+        partials.into_iter().map(|(record, _)| record).collect()
+    }
+}
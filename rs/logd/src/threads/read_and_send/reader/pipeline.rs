@@ -0,0 +1,411 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use super::cri::parse_cri_line;
+use super::dedup::{DedupOptions, Deduplicator};
+use super::docker::parse_docker_line;
+use super::multiline::{MultilineAggregator, MultilinePattern};
+use super::parser::RecordParser;
+use super::rate_limit::{RateCharges, RateLimiter};
+use super::record::{LogLine, LogRecord, LogStream, OutputFormat};
+use super::redact::Redactor;
+use super::ReaderError;
+
+// Turns the lines read from one file into encoded records. The state is kept across the
+// chunks of one read, offsets are relative to the position the read started at.
+pub struct Pipeline {
+    output_format: OutputFormat,
+    parser: RecordParser,
+    multiline: Option<MultilineAggregator>,
+    dedup: Option<Deduplicator>,
+    // emit records that are held back at EOF instead of reading them again
+    flush: bool,
+    // fraction of records that are shipped
+    sample_rate: Option<f64>,
+    redactor: Redactor,
+    rate_limiter: RateLimiter,
+    // key of the container in the rate limiter
+    container: String,
+    // applied once the records are committed, keyed by the offset of the record
+    rate_charges: RateCharges,
+    redactions: Vec<(u64, usize, u64)>,
+    // input bytes of all complete lines
+    consumed: u64,
+    // input bytes up to the first line that belongs to a record not emitted yet
+    clean: u64,
+}
+
+impl Pipeline {
+    pub fn new(output_format: OutputFormat, multiline: Option<MultilinePattern>) -> Self {
+        // records only exist in the structured format
+        let multiline = match output_format {
+            OutputFormat::Raw => None,
+            OutputFormat::Structured => multiline.map(MultilineAggregator::new),
+        };
+        Self {
+            output_format,
+            parser: RecordParser::default(),
+            multiline,
+            dedup: None,
+            flush: false,
+            sample_rate: None,
+            redactor: Redactor::default(),
+            rate_limiter: RateLimiter::default(),
+            container: String::new(),
+            rate_charges: RateCharges::default(),
+            redactions: Vec::new(),
+            consumed: 0,
+            clean: 0,
+        }
+    }
+
+    // records only exist in the structured format
+    pub fn with_dedup(mut self, dedup: Option<DedupOptions>) -> Self {
+        self.dedup = match self.output_format {
+            OutputFormat::Raw => None,
+            OutputFormat::Structured => dedup.map(Deduplicator::new),
+        };
+        self
+    }
+
+    pub fn with_flush(mut self, flush: bool) -> Self {
+        self.flush = flush;
+        self
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: Option<f64>) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter, container: &str) -> Self {
+        self.rate_limiter = rate_limiter;
+        self.container = container.to_string();
+        self
+    }
+
+    pub fn clean_offset(&self) -> u64 {
+        self.clean
+    }
+
+    // true if lines at the end of the input belong to a record that was not emitted
+    pub fn holds_back(&self) -> bool {
+        self.clean < self.consumed
+    }
+
+    // How long held back lines wait for more input. An incomplete record waits for the
+    // flush timeout, a pending repeat for the rest of the dedup window.
+    pub fn hold_timeout(&self, flush_timeout: Duration) -> Duration {
+        let multiline = self.multiline.as_ref().and_then(|m| m.pending_start());
+        let incomplete = self.parser.pending_start().or(multiline).is_some();
+        let dedup = self
+            .dedup
+            .as_ref()
+            .filter(|dedup| dedup.pending_start().is_some())
+            .map(Deduplicator::window);
+        match (incomplete, dedup) {
+            (true, Some(window)) => flush_timeout.max(window),
+            (false, Some(window)) => window,
+            _ => flush_timeout,
+        }
+    }
+
+    // Applies the redaction counts and rate limit charges of the records before the clean
+    // offset once they are delivered. Records read again after a failed upload are counted
+    // by that read.
+    pub fn commit(&mut self) {
+        let (clean, all) = (self.clean, self.clean == self.consumed);
+        let committed = |start: u64| all || start < clean;
+        let mut redactions = std::mem::take(&mut self.redactions);
+        redactions.retain(|&(start, rule, matches)| {
+            if committed(start) {
+                self.redactor.count(rule, matches);
+            }
+            !committed(start)
+        });
+        self.redactions = redactions;
+        self.rate_limiter
+            .commit(&self.container, &mut self.rate_charges, committed);
+    }
+
+    // Processes a complete line. Returns true if the clean offset advanced.
+    pub fn push_line(&mut self, line: &[u8], buffer: &mut Vec<u8>) -> Result<bool, ReaderError> {
+        let start = self.consumed;
+        self.consumed += line.len() as u64;
+
+        match self.output_format {
+            // secrets split over partial lines are redacted in the joined line
+            OutputFormat::Raw if !self.redactor.is_empty() => {
+                if let Some((record, record_start)) =
+                    self.parser.push(&String::from_utf8_lossy(line), start)
+                {
+                    self.emit_raw(record, record_start, false, buffer)?;
+                }
+            }
+            OutputFormat::Raw => self.push_raw(&raw_line(line), start, buffer)?,
+            OutputFormat::Structured => {
+                if let Some((record, record_start)) =
+                    self.parser.push(&String::from_utf8_lossy(line), start)
+                {
+                    self.emit(record, record_start, buffer)?;
+                }
+            }
+        }
+        Ok(self.advance())
+    }
+
+    // Called at EOF, emits held back records if the pipeline flushes.
+    // Returns true if the buffer grew and ends at a clean point.
+    pub fn finish(&mut self, buffer: &mut Vec<u8>) -> Result<bool, ReaderError> {
+        let mut advanced = false;
+        if self.flush && self.holds_back() {
+            for record in self.parser.flush() {
+                match self.output_format {
+                    OutputFormat::Raw => self.emit_raw(record, self.consumed, true, buffer)?,
+                    OutputFormat::Structured => self.emit(record, self.consumed, buffer)?,
+                }
+            }
+            let start = self.multiline.as_ref().and_then(|m| m.pending_start());
+            if let Some(record) = self.multiline.as_mut().and_then(MultilineAggregator::flush) {
+                self.deduplicate(record, start.unwrap_or(self.consumed), buffer)?;
+            }
+            let start = self.dedup.as_ref().and_then(|d| d.pending_start());
+            if let Some(record) = self.dedup.as_mut().and_then(Deduplicator::flush) {
+                self.encode(&record, start.unwrap_or(self.consumed), buffer)?;
+            }
+            advanced = self.advance();
+        }
+        // lines dropped at the end of the input are reported once nothing is held back
+        if !self.holds_back() && self.mark_dropped(self.consumed, buffer)? {
+            advanced = true;
+        }
+        Ok(advanced)
+    }
+
+    fn push_raw(
+        &mut self,
+        line: &[u8],
+        start: u64,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), ReaderError> {
+        if self.sampled(line) && self.admit_raw(line, start) {
+            self.mark_dropped(start, buffer)?;
+            buffer.extend_from_slice(line);
+        }
+        Ok(())
+    }
+
+    // Ships a record joined from partial lines as one CRI line, partial if it was flushed
+    // before its last line
+    fn emit_raw(
+        &mut self,
+        record: LogRecord,
+        start: u64,
+        partial: bool,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), ReaderError> {
+        let mut counts = Vec::new();
+        let message = self.redactor.redact_counted(&record.message, &mut counts);
+        self.count_redactions(start, counts);
+        let line = match (record.timestamp, record.stream) {
+            (Some(timestamp), Some(stream)) => LogLine {
+                timestamp: timestamp.into(),
+                stream,
+                partial,
+                message,
+            }
+            .to_cri(),
+            // not a CRI or docker line
+            _ => format!("{message}\n"),
+        };
+        self.push_raw(line.as_bytes(), start, buffer)
+    }
+
+    fn emit(
+        &mut self,
+        mut record: LogRecord,
+        start: u64,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), ReaderError> {
+        // secrets split over partial lines are redacted in the joined record
+        let mut counts = Vec::new();
+        let redacted = match self.redactor.redact_counted(&record.message, &mut counts) {
+            Cow::Owned(redacted) => Some(redacted),
+            Cow::Borrowed(_) => None,
+        };
+        self.count_redactions(start, counts);
+        if let Some(redacted) = redacted {
+            record.message = redacted;
+        }
+        // a joined record starts at the first line of the record
+        let record = match &mut self.multiline {
+            Some(multiline) => {
+                let pending_start = multiline.pending_start();
+                multiline
+                    .push(record, start)
+                    .map(|record| (record, pending_start.unwrap_or(start)))
+            }
+            None => Some((record, start)),
+        };
+        match record {
+            Some((record, start)) => self.deduplicate(record, start, buffer),
+            None => Ok(()),
+        }
+    }
+
+    fn deduplicate(
+        &mut self,
+        record: LogRecord,
+        start: u64,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), ReaderError> {
+        let record = match &mut self.dedup {
+            Some(dedup) => {
+                let pending_start = dedup.pending_start();
+                dedup
+                    .push(record, start)
+                    .map(|record| (record, pending_start.unwrap_or(start)))
+            }
+            None => Some((record, start)),
+        };
+        match record {
+            Some((record, start)) => self.encode(&record, start, buffer),
+            None => Ok(()),
+        }
+    }
+
+    fn count_redactions(&mut self, start: u64, counts: Vec<(usize, u64)>) {
+        self.redactions.extend(
+            counts
+                .into_iter()
+                .map(|(rule, matches)| (start, rule, matches)),
+        );
+    }
+
+    fn encode(
+        &mut self,
+        record: &LogRecord,
+        start: u64,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), ReaderError> {
+        let offset = buffer.len();
+        serde_json::to_writer(&mut *buffer, record)?;
+        let len = buffer.len() - offset;
+        if !self.sampled(&buffer[offset..])
+            || !self.rate_limiter.admit(
+                &self.container,
+                len,
+                record.timestamp.as_deref(),
+                start,
+                &mut self.rate_charges,
+            )
+        {
+            buffer.truncate(offset);
+            return Ok(());
+        }
+        // the marker for dropped records goes before the record
+        let encoded = buffer.split_off(offset);
+        self.mark_dropped(start, buffer)?;
+        buffer.extend_from_slice(&encoded);
+        buffer.push(b'\n');
+        Ok(())
+    }
+
+    fn admit_raw(&mut self, line: &[u8], start: u64) -> bool {
+        if self.rate_limiter.is_empty() {
+            return true;
+        }
+        let line = String::from_utf8_lossy(line);
+        let timestamp = parse_cri_line(&line).map(|line| line.timestamp);
+        self.rate_limiter.admit(
+            &self.container,
+            line.len(),
+            timestamp.as_deref(),
+            start,
+            &mut self.rate_charges,
+        )
+    }
+
+    // Writes a record with the number of lines dropped by the rate limit since the last one,
+    // before the record at start
+    fn mark_dropped(&mut self, start: u64, buffer: &mut Vec<u8>) -> Result<bool, ReaderError> {
+        let Some((dropped, timestamp)) =
+            self.rate_limiter
+                .take_dropped(&self.container, start, &mut self.rate_charges)
+        else {
+            return Ok(false);
+        };
+        let message = format!("{dropped} lines dropped due to rate limit");
+        match (self.output_format, timestamp) {
+            (OutputFormat::Raw, Some(timestamp)) => {
+                let line = LogLine {
+                    timestamp: timestamp.into(),
+                    stream: LogStream::Stderr,
+                    partial: false,
+                    message: message.into(),
+                };
+                buffer.extend_from_slice(line.to_cri().as_bytes());
+            }
+            (OutputFormat::Raw, None) => {
+                buffer.extend_from_slice(message.as_bytes());
+                buffer.push(b'\n');
+            }
+            (OutputFormat::Structured, timestamp) => {
+                let record = LogRecord {
+                    timestamp,
+                    stream: Some(LogStream::Stderr),
+                    message,
+                    repeat_count: None,
+                    last_timestamp: None,
+                };
+                serde_json::to_writer(&mut *buffer, &record)?;
+                buffer.push(b'\n');
+            }
+        }
+        Ok(true)
+    }
+
+    // The decision only depends on the content, a record that is read again after a failed
+    // upload is sampled the same way
+    fn sampled(&self, data: &[u8]) -> bool {
+        let Some(sample_rate) = self.sample_rate else {
+            return true;
+        };
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        (hasher.finish() as f64) < sample_rate * u64::MAX as f64
+    }
+
+    fn advance(&mut self) -> bool {
+        let multiline = self.multiline.as_ref().and_then(|m| m.pending_start());
+        let dedup = self.dedup.as_ref().and_then(|d| d.pending_start());
+        let clean = [self.parser.pending_start(), multiline, dedup]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(self.consumed);
+        if clean <= self.clean {
+            return false;
+        }
+        self.clean = clean;
+        true
+    }
+}
+
+// docker json-file lines are normalized to the CRI format
+fn raw_line(line: &[u8]) -> Cow<'_, [u8]> {
+    match line.first() {
+        Some(b'{') => match parse_docker_line(&String::from_utf8_lossy(line)) {
+            Some(docker) => Cow::Owned(docker.to_cri().into_bytes()),
+            None => Cow::Borrowed(line),
+        },
+        _ => Cow::Borrowed(line),
+    }
+}
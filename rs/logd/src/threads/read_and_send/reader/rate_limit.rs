@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::metrics::RATE_LIMITED_LINES;
+
+// Containers that were not seen for this long lose their bucket
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateLimit {
+    pub bytes_per_sec: Option<f64>,
+    pub lines_per_sec: Option<f64>,
+}
+
+impl RateLimit {
+    fn is_empty(&self) -> bool {
+        self.bytes_per_sec.is_none() && self.lines_per_sec.is_none()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateLimits {
+    // shared by all containers on the node
+    pub node: RateLimit,
+    pub container: RateLimit,
+}
+
+// Allows a burst of one second. A line larger than the burst is admitted by a full bucket
+// and takes it into debt, so the average rate still holds.
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+        }
+    }
+
+    // pending tokens are taken by records that are not committed yet
+    fn has_tokens(&mut self, cost: f64, pending: f64, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last_refill = now;
+        self.tokens - pending >= cost.min(self.rate)
+    }
+}
+
+#[derive(Default)]
+struct Buckets {
+    bytes: Option<TokenBucket>,
+    lines: Option<TokenBucket>,
+}
+
+impl Buckets {
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            bytes: limit.bytes_per_sec.map(TokenBucket::new),
+            lines: limit.lines_per_sec.map(TokenBucket::new),
+        }
+    }
+
+    fn has_tokens(&mut self, bytes: usize, pending: &RateCharges, now: Instant) -> bool {
+        let bytes = self
+            .bytes
+            .as_mut()
+            .is_none_or(|bucket| bucket.has_tokens(bytes as f64, pending.bytes, now));
+        let lines = self
+            .lines
+            .as_mut()
+            .is_none_or(|bucket| bucket.has_tokens(1.0, pending.lines, now));
+        bytes && lines
+    }
+
+    fn take(&mut self, bytes: usize) {
+        if let Some(bucket) = &mut self.bytes {
+            bucket.tokens -= bytes as f64;
+        }
+        if let Some(bucket) = &mut self.lines {
+            bucket.tokens -= 1.0;
+        }
+    }
+}
+
+enum Charge {
+    Admitted(usize),
+    // the scope of the bucket that dropped the line, and its timestamp
+    Dropped(&'static str, Option<String>),
+    Marked,
+}
+
+// Tokens taken and lines dropped by the records of one read, keyed by the offset of the
+// record. They are applied to the buckets once the records are committed, so a read that is
+// retried after a failed upload is charged once.
+#[derive(Default)]
+pub struct RateCharges {
+    charges: Vec<(u64, Charge)>,
+    bytes: f64,
+    lines: f64,
+    // lines dropped since the last marker, and the timestamp of the last one
+    dropped: u64,
+    dropped_timestamp: Option<String>,
+    // a marker of the read reported the committed drops
+    marked: bool,
+}
+
+impl RateCharges {
+    fn push(&mut self, start: u64, charge: Charge) {
+        match &charge {
+            Charge::Admitted(bytes) => {
+                self.bytes += *bytes as f64;
+                self.lines += 1.0;
+            }
+            Charge::Dropped(_, timestamp) => {
+                self.dropped += 1;
+                self.dropped_timestamp = timestamp.clone();
+            }
+            Charge::Marked => {
+                self.dropped = 0;
+                self.dropped_timestamp = None;
+                self.marked = true;
+            }
+        }
+        self.charges.push((start, charge));
+    }
+
+    // Removes the charges of the committed records, in order
+    fn split(&mut self, committed: impl Fn(u64) -> bool) -> Vec<Charge> {
+        let charges = std::mem::take(self).charges;
+        let mut done = Vec::new();
+        for (start, charge) in charges {
+            if committed(start) {
+                done.push(charge);
+            } else {
+                self.push(start, charge);
+            }
+        }
+        done
+    }
+}
+
+struct Container {
+    buckets: Buckets,
+    // lines dropped since the last marker, and the timestamp of the last one
+    dropped: u64,
+    dropped_timestamp: Option<String>,
+    last_seen: Instant,
+}
+
+struct State {
+    node: Buckets,
+    containers: HashMap<String, Container>,
+    last_prune: Instant,
+}
+
+// Token buckets for the node and for each container, shared by the reads of all files
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(RateLimits::default())
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            state: Arc::new(Mutex::new(State {
+                node: Buckets::new(&limits.node),
+                containers: HashMap::new(),
+                last_prune: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.limits.node.is_empty() && self.limits.container.is_empty()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn container<'a>(
+        &self,
+        containers: &'a mut HashMap<String, Container>,
+        container: &str,
+        now: Instant,
+    ) -> &'a mut Container {
+        let entry = containers
+            .entry(container.to_string())
+            .or_insert_with(|| Container {
+                buckets: Buckets::new(&self.limits.container),
+                dropped: 0,
+                dropped_timestamp: None,
+                last_seen: now,
+            });
+        entry.last_seen = now;
+        entry
+    }
+
+    // Decides if a line of the container starting at start is admitted, the tokens are taken
+    // once it is committed. A dropped line is counted for the marker.
+    pub fn admit(
+        &self,
+        container: &str,
+        bytes: usize,
+        timestamp: Option<&str>,
+        start: u64,
+        charges: &mut RateCharges,
+    ) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let now = Instant::now();
+        let mut state = self.lock();
+        if now.duration_since(state.last_prune) > IDLE_TIMEOUT {
+            state.containers.retain(|_, container| {
+                container.dropped > 0 || now.duration_since(container.last_seen) < IDLE_TIMEOUT
+            });
+            state.last_prune = now;
+        }
+
+        let State {
+            node, containers, ..
+        } = &mut *state;
+        let entry = self.container(containers, container, now);
+        let scope = if !entry.buckets.has_tokens(bytes, charges, now) {
+            "container"
+        } else if !node.has_tokens(bytes, charges, now) {
+            "node"
+        } else {
+            charges.push(start, Charge::Admitted(bytes));
+            return true;
+        };
+        charges.push(start, Charge::Dropped(scope, timestamp.map(str::to_string)));
+        false
+    }
+
+    // Lines of the container dropped since the last marker, and the timestamp of the last
+    // one, for a marker written before the record at start
+    pub fn take_dropped(
+        &self,
+        container: &str,
+        start: u64,
+        charges: &mut RateCharges,
+    ) -> Option<(u64, Option<String>)> {
+        if self.is_empty() {
+            return None;
+        }
+        let (committed, timestamp) = match self.lock().containers.get(container) {
+            Some(entry) if !charges.marked => (entry.dropped, entry.dropped_timestamp.clone()),
+            _ => (0, None),
+        };
+        let dropped = committed + charges.dropped;
+        if dropped == 0 {
+            return None;
+        }
+        let timestamp = match charges.dropped {
+            0 => timestamp,
+            _ => charges.dropped_timestamp.clone(),
+        };
+        charges.push(start, Charge::Marked);
+        Some((dropped, timestamp))
+    }
+
+    // Applies the charges of the records for which committed is true
+    pub fn commit(
+        &self,
+        container: &str,
+        charges: &mut RateCharges,
+        committed: impl Fn(u64) -> bool,
+    ) {
+        let charges = charges.split(committed);
+        if charges.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let mut state = self.lock();
+        let State {
+            node, containers, ..
+        } = &mut *state;
+        let entry = self.container(containers, container, now);
+        for charge in charges {
+            match charge {
+                Charge::Admitted(bytes) => {
+                    entry.buckets.take(bytes);
+                    node.take(bytes);
+                }
+                Charge::Dropped(scope, timestamp) => {
+                    RATE_LIMITED_LINES.with_label_values(&[scope]).inc();
+                    entry.dropped += 1;
+                    entry.dropped_timestamp = timestamp;
+                }
+                Charge::Marked => {
+                    entry.dropped = 0;
+                    entry.dropped_timestamp = None;
+                }
+            }
+        }
+    }
+}
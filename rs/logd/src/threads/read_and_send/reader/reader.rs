@@ -1,10 +1,12 @@
 use bytes::Bytes;
+use flate2::read::MultiGzDecoder;
 use std::{
     fs::File,
-    io::{BufRead, BufReader, Seek},
+    io::{self, BufRead, BufReader, Read, Seek},
 };
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::Sender as BytesSender;
 
+use super::pipeline::Pipeline;
 use super::ReaderError;
 
 #[cfg(test)]
@@ -34,32 +36,65 @@ pub fn get_reader(mut file: File, position: u64) -> Result<BufReader<File>, std:
     Ok(BufReader::new(file))
 }
 
+// position is an offset into the decompressed data
+pub fn get_gz_reader(
+    file: File,
+    position: u64,
+) -> Result<BufReader<MultiGzDecoder<File>>, std::io::Error> {
+    let mut reader = BufReader::new(MultiGzDecoder::new(file));
+    io::copy(&mut reader.by_ref().take(position), &mut io::sink())?;
+    Ok(reader)
+}
+
+pub struct Chunk {
+    // input bytes consumed up to the end of the last complete record
+    pub bytes_read: usize,
+    // encoded bytes sent to the upload stream
+    pub bytes_sent: usize,
+    // no more complete lines are available
+    pub eof: bool,
+}
+
+// Reads whole lines until batch_size bytes are encoded. Lines of a record that is held back
+// at EOF are not counted in bytes_read, so they are read again once the record is complete.
+// The chunk is sent as one message, tx needs room for it.
 pub fn read_chunk(
     reader: &mut impl BufRead,
     batch_size: usize,
-    tx: UnboundedSender<Result<Bytes, hyper::Error>>,
-) -> Result<(), ReaderError> {
-    let mut buffer = String::with_capacity(batch_size);
-    let mut line = String::new();
-    loop {
-        buffer.clear();
-        let mut bytes_read = 0;
-        while bytes_read < batch_size {
-            line.clear();
-            // if line exceeds batch_size, buffer will grow larger than batch_size
-            match reader.read_line(&mut line) {
-                Ok(0) => break, // EOF reached
-                Ok(n) => {
-                    bytes_read += n;
-                    buffer.push_str(&line);
-                }
-                Err(error) => return Err(ReaderError::Io(error)),
-            }
+    pipeline: &mut Pipeline,
+    tx: BytesSender<Result<Bytes, hyper::Error>>,
+) -> Result<Chunk, ReaderError> {
+    let mut buffer = Vec::with_capacity(batch_size);
+    let mut line = Vec::new();
+    let clean_offset = pipeline.clean_offset();
+    let mut bytes_sent = 0;
+    let eof = loop {
+        // if line exceeds batch_size, buffer will grow larger than batch_size
+        if buffer.len() >= batch_size {
+            bytes_sent = buffer.len();
+            break false;
+        }
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)?;
+        // the last line is still being written
+        if n == 0 || line.last() != Some(&b'\n') {
+            break true;
         }
-        if bytes_read == 0 {
-            break;
+        if pipeline.push_line(&line, &mut buffer)? {
+            bytes_sent = buffer.len();
         }
-        tx.send(Ok(Bytes::copy_from_slice(buffer.as_bytes())))?;
+    };
+    if eof && pipeline.finish(&mut buffer)? {
+        bytes_sent = buffer.len();
     }
-    Ok(())
+    // records that completed after the held back record are read again
+    buffer.truncate(bytes_sent);
+    if !buffer.is_empty() {
+        tx.try_send(Ok(Bytes::from(buffer)))?;
+    }
+    Ok(Chunk {
+        bytes_read: (pipeline.clean_offset() - clean_offset) as usize,
+        bytes_sent,
+        eof,
+    })
 }
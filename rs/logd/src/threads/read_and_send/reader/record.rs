@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl fmt::Display for LogStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdout => write!(f, "stdout"),
+            Self::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+impl FromStr for LogStream {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stdout" => Ok(Self::Stdout),
+            "stderr" => Ok(Self::Stderr),
+            _ => Err(()),
+        }
+    }
+}
+
+// A single line written by the container runtime, in any supported log format
+#[derive(Debug, PartialEq)]
+pub struct LogLine<'a> {
+    pub timestamp: Cow<'a, str>,
+    pub stream: LogStream,
+    // the line continues in the next line of the same stream
+    pub partial: bool,
+    pub message: Cow<'a, str>,
+}
+
+impl LogLine<'_> {
+    // <RFC3339Nano> <stdout|stderr> <P|F> <message>
+    pub fn to_cri(&self) -> String {
+        let tag = if self.partial { "P" } else { "F" };
+        format!(
+            "{} {} {} {}\n",
+            self.timestamp, self.stream, tag, self.message
+        )
+    }
+}
+
+// A log line with its container runtime metadata
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<LogStream>,
+    pub message: String,
+    // consecutive repeats collapsed into this record, including itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_timestamp: Option<String>,
+}
+
+impl LogRecord {
+    // A line without runtime metadata
+    pub fn plain(message: &str) -> Self {
+        Self {
+            timestamp: None,
+            stream: None,
+            message: message.to_string(),
+            repeat_count: None,
+            last_timestamp: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputFormat {
+    // Lines are shipped in the CRI format, as the kubelet writes them
+    #[default]
+    Raw,
+    // One JSON record per line with timestamp, stream and message
+    Structured,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Raw => write!(f, "raw"),
+            Self::Structured => write!(f, "structured"),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "raw" => Ok(Self::Raw),
+            "structured" => Ok(Self::Structured),
+            other => Err(format!("unknown output format: {other}")),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::borrow::Cow;
+
+use crate::metrics::REDACTIONS;
+
+// Built-in detectors: name, pattern, replacement. Groups in the pattern keep the context
+// of a secret, e.g. the key of a key=value pair.
+const BUILTIN_RULES: [(&str, &str, &str); 6] = [
+    (
+        "jwt",
+        r"\beyJ[A-Za-z0-9_-]+\.eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+",
+        "[REDACTED:jwt]",
+    ),
+    (
+        "bearer",
+        r"(?i)(\bbearer\s+)[A-Za-z0-9._~+/-]+=*",
+        "${1}[REDACTED:bearer]",
+    ),
+    (
+        "aws-key",
+        r#"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b|(?i)(aws_secret_access_key["']?\s*[:=]\s*["']?)[A-Za-z0-9/+=]{40}"#,
+        "${1}[REDACTED:aws-key]",
+    ),
+    (
+        "credit-card",
+        r"\b(?:\d[ -]?){12,18}\d\b",
+        "[REDACTED:credit-card]",
+    ),
+    (
+        "email",
+        r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b",
+        "[REDACTED:email]",
+    ),
+    (
+        "url-password",
+        r"(://[^:/@\s]+:)[^@\s]+@",
+        "${1}[REDACTED]@",
+    ),
+];
+
+// A user defined rule, as configured in LOGD_REDACT_RULES
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct RedactionRuleConfig {
+    pub name: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct RedactionRule {
+    name: String,
+    pattern: Regex,
+    replacement: String,
+}
+
+// Replaces secrets and personal data in log lines before they are uploaded
+#[derive(Clone, Debug, Default)]
+pub struct Redactor {
+    rules: Vec<RedactionRule>,
+}
+
+impl Redactor {
+    // names is a comma separated list of built-in detectors or "all"
+    pub fn new(names: &str, custom: Vec<RedactionRuleConfig>) -> Result<Self, String> {
+        let names: Vec<&str> = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect();
+        let mut rules = Vec::new();
+        for name in &names {
+            if *name != "all" && !BUILTIN_RULES.iter().any(|(builtin, _, _)| builtin == name) {
+                return Err(format!("unknown redaction rule: {name}"));
+            }
+        }
+        for (name, pattern, replacement) in BUILTIN_RULES {
+            if names.contains(&"all") || names.contains(&name) {
+                rules.push(RedactionRule {
+                    name: name.to_string(),
+                    pattern: Regex::new(pattern).unwrap(),
+                    replacement: replacement.to_string(),
+                });
+            }
+        }
+        for rule in custom {
+            let pattern =
+                Regex::new(&rule.pattern).map_err(|e| format!("rule {}: {e}", rule.name))?;
+            let replacement = rule
+                .replacement
+                .unwrap_or_else(|| format!("[REDACTED:{}]", rule.name));
+            rules.push(RedactionRule {
+                name: rule.name,
+                pattern,
+                replacement,
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    #[cfg(test)]
+    pub fn redact<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        self.redact_counted(line, &mut Vec::new())
+    }
+
+    // Also collects the matches of each rule, they are counted once the record is committed
+    pub fn redact_counted<'a>(
+        &self,
+        line: &'a str,
+        counts: &mut Vec<(usize, u64)>,
+    ) -> Cow<'a, str> {
+        let mut line = Cow::Borrowed(line);
+        for (index, rule) in self.rules.iter().enumerate() {
+            let mut count = 0;
+            let redacted = rule.pattern.replace_all(&line, |captures: &Captures| {
+                // digit sequences are only card numbers if the checksum matches
+                if rule.name == "credit-card" && !luhn_valid(&captures[0]) {
+                    return captures[0].to_string();
+                }
+                count += 1;
+                let mut replacement = String::new();
+                captures.expand(&rule.replacement, &mut replacement);
+                replacement
+            });
+            if count > 0 {
+                counts.push((index, count));
+                line = Cow::Owned(redacted.into_owned());
+            }
+        }
+        line
+    }
+
+    pub fn count(&self, rule: usize, matches: u64) {
+        REDACTIONS
+            .with_label_values(&[&self.rules[rule].name])
+            .inc_by(matches);
+    }
+}
+
+fn luhn_valid(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| match i % 2 {
+            0 => digit,
+            _ if digit * 2 > 9 => digit * 2 - 9,
+            _ => digit * 2,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::constant::{UPLOAD_RETRY_BACKOFF_MAX, UPLOAD_RETRY_BACKOFF_MIN};
+
+struct Retry {
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+// Files whose last upload failed, retried with exponential backoff
+#[derive(Default)]
+pub struct RetryQueue {
+    retries: HashMap<PathBuf, Retry>,
+}
+
+impl RetryQueue {
+    pub fn schedule(&mut self, path: &Path) {
+        let retry = self.retries.entry(path.to_path_buf()).or_insert(Retry {
+            attempts: 0,
+            next_attempt: Instant::now(),
+        });
+        let backoff = UPLOAD_RETRY_BACKOFF_MIN
+            .saturating_mul(2u32.saturating_pow(retry.attempts))
+            .min(UPLOAD_RETRY_BACKOFF_MAX);
+        retry.attempts += 1;
+        retry.next_attempt = Instant::now() + backoff;
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.retries.remove(path);
+    }
+
+    // true while the file backs off, new events for it are picked up by the retry
+    pub fn is_waiting(&self, path: &Path) -> bool {
+        self.retries
+            .get(path)
+            .is_some_and(|retry| Instant::now() < retry.next_attempt)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.retries.is_empty()
+    }
+
+    pub fn due(&self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        self.retries
+            .iter()
+            .filter(|(_, retry)| retry.next_attempt <= now)
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
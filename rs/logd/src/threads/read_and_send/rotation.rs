@@ -0,0 +1,79 @@
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+use crate::checkpoint::{Checkpoint, CheckpointStore};
+
+pub enum Resume {
+    // Continue reading the file at this offset
+    At(u64),
+    // The path points to a new file, the previous inode has to be drained first
+    Rotated(Checkpoint),
+}
+
+pub fn resume_position(path: &Path, metadata: &Metadata, checkpoints: &CheckpointStore) -> Resume {
+    let checkpoint = match checkpoints.get(path) {
+        Some(checkpoint) if !checkpoint.matches(metadata) => return Resume::Rotated(checkpoint),
+        Some(checkpoint) => checkpoint,
+        // A rotated file (0.log -> 0.log.<timestamp>) keeps the offset of its inode
+        None => match checkpoints.find_moved(path, metadata) {
+            Some(checkpoint) => {
+                debug!(
+                    "{} was renamed to {}",
+                    checkpoint.path.display(),
+                    path.display()
+                );
+                checkpoints.remove(&checkpoint.path);
+                checkpoints.commit(path, metadata, checkpoint.offset);
+                checkpoint
+            }
+            None => return Resume::At(0),
+        },
+    };
+
+    if metadata.len() < checkpoint.offset {
+        info!(
+            "{} was truncated from {} to {} bytes, reading from start",
+            path.display(),
+            checkpoint.offset,
+            metadata.len()
+        );
+        return Resume::At(0);
+    }
+    Resume::At(checkpoint.offset)
+}
+
+// Finds the file that still holds the inode of a checkpoint, e.g. 0.log.<timestamp>
+pub fn find_rotated_file(path: &Path, previous: &Checkpoint) -> Option<PathBuf> {
+    let parent = path.parent()?;
+    fs::read_dir(parent)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|candidate| {
+            candidate != path
+                && fs::metadata(candidate).is_ok_and(|metadata| previous.matches(&metadata))
+        })
+}
+
+// Oldest compressed rotation of path (0.log.<timestamp>.gz) that has not been shipped
+pub fn find_compressed_rotation(path: &Path, checkpoints: &CheckpointStore) -> Option<PathBuf> {
+    let parent = path.parent()?;
+    let prefix = format!("{}.", path.file_name()?.to_str()?);
+    let mut candidates: Vec<PathBuf> = fs::read_dir(parent)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|candidate| {
+            candidate
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".gz"))
+                && checkpoints.get(candidate).is_none()
+                && checkpoints.get(&candidate.with_extension("")).is_none()
+        })
+        .collect();
+    // kubelet timestamps sort chronologically
+    candidates.sort();
+    candidates.into_iter().next()
+}
@@ -0,0 +1,57 @@
+use serde::Serialize;
+use std::path::Path;
+
+use crate::filter::{is_docker_container_id, parse_pod_dir};
+
+// Docker keeps container state next to the logs in /var/lib/docker/containers/<id>/,
+// only <id>-json.log and its rotations (<id>-json.log.1) are log files
+pub fn is_log_file(path: &Path) -> bool {
+    let Some(container_id) = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .and_then(|name| name.to_str())
+        .filter(|name| is_docker_container_id(name))
+    else {
+        return true;
+    };
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(&format!("{container_id}-json.log")))
+}
+
+// Container of a log file in the kubelet layout:
+// /var/log/pods/<namespace>_<pod>_<uid>/<container>/<restart>.log
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PodLogSource {
+    pub namespace: String,
+    pub pod_name: String,
+    pub pod_uid: String,
+    pub container_name: String,
+    pub restart_count: u32,
+}
+
+impl PodLogSource {
+    // None for other layouts, e.g. docker json-file logs
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let container_path = path.parent()?;
+        let container_name = container_path.file_name()?.to_str()?;
+        let pod_dir = container_path.parent()?.file_name()?.to_str()?;
+
+        // rotations keep the restart count: 0.log.20240101-000000.gz
+        let restart_count = file_name.split_once(".log")?.0.parse().ok()?;
+
+        let (namespace, pod_name, pod_uid) = parse_pod_dir(pod_dir)?;
+        if container_name.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            namespace: namespace.to_string(),
+            pod_name: pod_name.to_string(),
+            pod_uid: pod_uid.to_string(),
+            container_name: container_name.to_string(),
+            restart_count,
+        })
+    }
+}
@@ -1,15 +1,26 @@
 #[cfg(test)]
 mod integration_tests {
-    use shared::client::MockHik8sClient;
+    use k8s_openapi::api::core::v1::Pod;
+    use shared::client::{Client, Compression, Hik8sClientError, MockHik8sClient};
     use std::collections::HashSet;
-    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     use std::sync::{mpsc, Arc, Mutex};
     use std::time::Duration;
     use tempfile::tempdir;
     use tracing::debug;
 
-    use crate::threads::read_and_send::{read_file_and_send_data, ReadThreadError};
-    use crate::util::test::test_util::create_test_file;
+    use crate::checkpoint::CheckpointStore;
+    use crate::metrics::{FILE_LAG, REJECTED_CHUNKS, UPLOAD_FAILURES};
+    use crate::pods::PodCache;
+    use crate::spool::{Spool, SpoolOptions};
+    use crate::threads::read_and_send::budget::ByteBudget;
+    use crate::threads::read_and_send::metadata::UploadMetadata;
+    use crate::threads::read_and_send::reader::{DedupOptions, MultilinePattern, OutputFormat};
+    use crate::threads::read_and_send::source::PodLogSource;
+    use crate::threads::read_and_send::{read_file_and_send_data, ReadOptions, ReadThreadError};
+    use crate::util::test::test_util::{create_test_file, write_to_existing_file};
+    use shared::health::{Health, Heartbeat, READY_CAUGHT_UP};
     use shared::tracing::setup_tracing;
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -33,18 +44,31 @@ mod integration_tests {
         let received_data = Arc::new(Mutex::new(Vec::new()));
         let client = MockHik8sClient::new(Arc::clone(&received_data));
 
+        // Checkpoints are persisted next to the test files
+        let checkpoint_path = temp_path.join("checkpoints.json");
+        let checkpoints = CheckpointStore::load(&checkpoint_path, Duration::from_secs(60))?;
+        let checkpoints_clone = checkpoints.clone();
+
         // Spawn a thread to run the read_file_and_send_data function
         let termination_signal = Arc::new(AtomicBool::new(false));
         let termination_signal_clone = Arc::clone(&termination_signal);
         let handle = tokio::spawn(async move {
-            read_file_and_send_data(receiver, client, termination_signal_clone)
-                .await
-                .expect("Failed to read and send data");
+            read_file_and_send_data(
+                receiver,
+                client,
+                checkpoints_clone,
+                ReadOptions::default(),
+                PodCache::default(),
+                Heartbeat::default(),
+                termination_signal_clone,
+            )
+            .await
+            .expect("Failed to read and send data");
         });
 
         // Send two path with data
         let mut paths = HashSet::new();
-        paths.insert(file1_path);
+        paths.insert(file1_path.clone());
         paths.insert(file2_path);
         sender.send(paths).unwrap();
 
@@ -69,6 +93,915 @@ mod integration_tests {
             received_data.lock().unwrap();
         assert!(!data.is_empty(), "No data received by the mock client");
         assert_eq!(data.len(), 3);
+
+        // Verify that the offsets were persisted on shutdown
+        let file1_len = std::fs::metadata(&file1_path)?.len();
+        assert_eq!(checkpoints.get(&file1_path).unwrap().offset, file1_len);
+        let reloaded = CheckpointStore::load(&checkpoint_path, Duration::from_secs(60))?;
+        assert_eq!(reloaded.get(&file1_path), checkpoints.get(&file1_path));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_read_file_and_send_data_ready_without_uploads() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+
+        // A restart finds every file shipped up to its persisted offset
+        let temp_dir = tempdir()?;
+        let file_path = create_test_file(temp_dir.path(), "0.log")?;
+        let checkpoints = CheckpointStore::load(
+            &temp_dir.path().join("checkpoints.json"),
+            Duration::from_secs(60),
+        )?;
+        let file_metadata = std::fs::metadata(&file_path)?;
+        checkpoints.commit(&file_path, &file_metadata, file_metadata.len());
+
+        let health = Health::new();
+        health.require(READY_CAUGHT_UP);
+        let heartbeat = health.heartbeat("read_file_and_send_data", None);
+
+        let (sender, receiver) = mpsc::channel();
+        let received_data = Arc::new(Mutex::new(Vec::new()));
+        let client = MockHik8sClient::new(Arc::clone(&received_data));
+        let options = ReadOptions {
+            recv_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let termination_signal = Arc::new(AtomicBool::new(false));
+        let termination_signal_clone = Arc::clone(&termination_signal);
+        let handle = tokio::spawn(async move {
+            read_file_and_send_data(
+                receiver,
+                client,
+                checkpoints,
+                options,
+                PodCache::default(),
+                heartbeat,
+                termination_signal_clone,
+            )
+            .await
+        });
+
+        sender.send(HashSet::from([file_path])).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        termination_signal.store(true, Ordering::SeqCst);
+        handle.await.unwrap()?;
+
+        assert!(received_data.lock().unwrap().is_empty());
+        assert!(health.readiness().is_ok());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_read_file_and_send_data_retries_failed_upload() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let file1_path = create_test_file(&temp_path, "file1.txt")?;
+
+        let (sender, receiver) = mpsc::channel();
+
+        // The first upload is rejected
+        let received_data = Arc::new(Mutex::new(Vec::new()));
+        let client = MockHik8sClient::new(Arc::clone(&received_data))
+            .with_failures(1)
+            .with_failure_status(reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        let failures = UPLOAD_FAILURES.with_label_values(&["503"]).get();
+
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let checkpoints_clone = checkpoints.clone();
+
+        let termination_signal = Arc::new(AtomicBool::new(false));
+        let termination_signal_clone = Arc::clone(&termination_signal);
+        let handle = tokio::spawn(async move {
+            read_file_and_send_data(
+                receiver,
+                client,
+                checkpoints_clone,
+                ReadOptions::default(),
+                PodCache::default(),
+                Heartbeat::default(),
+                termination_signal_clone,
+            )
+            .await
+            .expect("Failed to read and send data");
+        });
+
+        let mut paths = HashSet::new();
+        paths.insert(file1_path.clone());
+        sender.send(paths).unwrap();
+
+        // The failed chunk is not committed
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(received_data.lock().unwrap().is_empty());
+        assert!(checkpoints.get(&file1_path).is_none());
+
+        // It is retried from the last committed offset without a new file event
+        let start = std::time::Instant::now();
+        while received_data.lock().unwrap().is_empty() && start.elapsed() < Duration::from_secs(3) {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        termination_signal.store(true, Ordering::SeqCst);
+        handle.await.unwrap();
+
+        assert_eq!(received_data.lock().unwrap().len(), 1);
+        let file1_len = std::fs::metadata(&file1_path)?.len();
+        assert_eq!(checkpoints.get(&file1_path).unwrap().offset, file1_len);
+
+        // The failure is counted by status and the file has no lag once it is uploaded
+        assert!(UPLOAD_FAILURES.with_label_values(&["503"]).get() > failures);
+        let lag = FILE_LAG.with_label_values(&[&file1_path.to_string_lossy()]);
+        assert_eq!(lag.get(), 0);
+        Ok(())
+    }
+
+    fn spool_options(path: &Path) -> SpoolOptions {
+        SpoolOptions {
+            path: path.to_path_buf(),
+            max_bytes: 1 << 20,
+            max_age: Duration::from_secs(3600),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_read_file_and_send_data_spools_failed_upload() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let file1_path = create_test_file(&temp_path, "file1.txt")?;
+        let spool_path = temp_path.join("spool");
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let options = ReadOptions {
+            spool: Some(spool_options(&spool_path)),
+            ..Default::default()
+        };
+
+        // The upload and the first replay are rejected
+        let thread = ReadThread::start_with_client(checkpoints.clone(), options, |client| {
+            client.with_failures(2)
+        });
+
+        // The chunk is committed once it is spooled
+        assert_eq!(thread.send_and_wait(&file1_path, 0), 0);
+        let file1_len = std::fs::metadata(&file1_path)?.len();
+        assert_eq!(checkpoints.get(&file1_path).unwrap().offset, file1_len);
+        assert_eq!(std::fs::read_dir(&spool_path)?.count(), 1);
+
+        // It is replayed after the backoff
+        assert_eq!(thread.wait(1), 1);
+        thread.stop().await;
+        assert_eq!(std::fs::read_dir(&spool_path)?.count(), 0);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_spooled_chunk_does_not_hold_back_uploads() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let file1_path = create_test_file(&temp_path, "file1.txt")?;
+        let spool_path = temp_path.join("spool");
+        let mut spool = Spool::open(spool_options(&spool_path))?;
+        spool.push(&serde_json::json!({"file": "0.log"}), b"spooled line\n")?;
+        drop(spool);
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let options = ReadOptions {
+            spool: Some(spool_options(&spool_path)),
+            ..Default::default()
+        };
+
+        // The replay of the spooled chunk fails, new chunks are uploaded before it
+        let thread = ReadThread::start_with_client(checkpoints.clone(), options, |client| {
+            client
+                .with_failures(1)
+                .with_failure_status(reqwest::StatusCode::SERVICE_UNAVAILABLE)
+        });
+        assert_eq!(thread.send_and_wait(&file1_path, 1), 1);
+        let file1_len = std::fs::metadata(&file1_path)?.len();
+        assert_eq!(checkpoints.get(&file1_path).unwrap().offset, file1_len);
+        assert_eq!(std::fs::read_dir(&spool_path)?.count(), 1);
+
+        assert_eq!(thread.wait(2), 2);
+        thread.stop().await;
+        assert_eq!(std::fs::read_dir(&spool_path)?.count(), 0);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rejected_chunks_are_dropped() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let file1_path = create_test_file(&temp_path, "file1.txt")?;
+        let spool_path = temp_path.join("spool");
+        let mut spool = Spool::open(spool_options(&spool_path))?;
+        spool.push(&serde_json::json!({"file": "0.log"}), b"spooled line\n")?;
+        drop(spool);
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let options = ReadOptions {
+            spool: Some(spool_options(&spool_path)),
+            ..Default::default()
+        };
+        let rejected = REJECTED_CHUNKS.with_label_values(&["413"]).get();
+
+        // The spooled chunk and the new chunk are too large, they are neither retried nor
+        // spooled
+        let thread = ReadThread::start_with_client(checkpoints.clone(), options, |client| {
+            client
+                .with_failures(2)
+                .with_failure_status(reqwest::StatusCode::PAYLOAD_TOO_LARGE)
+        });
+        assert_eq!(thread.send_and_wait(&file1_path, 1), 0);
+        let file1_len = std::fs::metadata(&file1_path)?.len();
+        assert_eq!(checkpoints.get(&file1_path).unwrap().offset, file1_len);
+        assert_eq!(std::fs::read_dir(&spool_path)?.count(), 0);
+        assert_eq!(
+            REJECTED_CHUNKS.with_label_values(&["413"]).get(),
+            rejected + 2
+        );
+
+        // The file is shipped from the next line
+        write_to_existing_file(&file1_path, "This line is shipped.")?;
+        assert_eq!(thread.send_and_wait(&file1_path, 1), 1);
+        thread.stop().await;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_read_file_and_send_data_falls_back_to_uncompressed() -> Result<(), ReadThreadError>
+    {
+        setup_tracing()?;
+
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let file1_path = create_test_file(&temp_path, "file1.txt")?;
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let options = ReadOptions {
+            compression: Compression::Gzip,
+            ..Default::default()
+        };
+
+        // The server does not accept compressed uploads
+        let thread = ReadThread::start_with_client(checkpoints.clone(), options, |client| {
+            client
+                .with_failures(1)
+                .with_failure_status(reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        });
+
+        // The chunk is uploaded again without compression
+        assert_eq!(thread.send_and_wait(&file1_path, 1), 1);
+        thread.stop().await;
+        let file1_len = std::fs::metadata(&file1_path)?.len();
+        assert_eq!(checkpoints.get(&file1_path).unwrap().offset, file1_len);
+        Ok(())
+    }
+
+    type ReceivedData = Arc<Mutex<Vec<reqwest::multipart::Form>>>;
+
+    struct ReadThread {
+        sender: mpsc::Sender<HashSet<PathBuf>>,
+        received_data: ReceivedData,
+        termination_signal: Arc<AtomicBool>,
+        handle: tokio::task::JoinHandle<()>,
+    }
+
+    impl ReadThread {
+        fn start(checkpoints: CheckpointStore) -> Self {
+            Self::start_with_options(checkpoints, ReadOptions::default())
+        }
+
+        fn start_with_options(checkpoints: CheckpointStore, options: ReadOptions) -> Self {
+            Self::start_with_pods(checkpoints, options, PodCache::default())
+        }
+
+        fn start_with_pods(
+            checkpoints: CheckpointStore,
+            options: ReadOptions,
+            pods: PodCache,
+        ) -> Self {
+            Self::spawn(checkpoints, options, pods, |client| client)
+        }
+
+        // The client is configured before the thread starts, e.g. to reject uploads
+        fn start_with_client(
+            checkpoints: CheckpointStore,
+            options: ReadOptions,
+            configure: impl FnOnce(MockHik8sClient) -> MockHik8sClient,
+        ) -> Self {
+            Self::spawn(checkpoints, options, PodCache::default(), configure)
+        }
+
+        fn spawn(
+            checkpoints: CheckpointStore,
+            options: ReadOptions,
+            pods: PodCache,
+            configure: impl FnOnce(MockHik8sClient) -> MockHik8sClient,
+        ) -> Self {
+            let (sender, receiver) = mpsc::channel();
+            let received_data = Arc::new(Mutex::new(Vec::new()));
+            let client = configure(MockHik8sClient::new(Arc::clone(&received_data)));
+            let termination_signal = Arc::new(AtomicBool::new(false));
+            let termination_signal_clone = Arc::clone(&termination_signal);
+            let handle = tokio::spawn(async move {
+                read_file_and_send_data(
+                    receiver,
+                    client,
+                    checkpoints,
+                    options,
+                    pods,
+                    Heartbeat::default(),
+                    termination_signal_clone,
+                )
+                .await
+                .expect("Failed to read and send data");
+            });
+            Self {
+                sender,
+                received_data,
+                termination_signal,
+                handle,
+            }
+        }
+
+        // Sends a file event and waits until the thread has uploaded `expected` forms in total
+        fn send_and_wait(&self, path: &Path, expected: usize) -> usize {
+            self.sender
+                .send(HashSet::from([path.to_path_buf()]))
+                .unwrap();
+            self.wait(expected)
+        }
+
+        // Waits until the thread has uploaded `expected` forms in total
+        fn wait(&self, expected: usize) -> usize {
+            let start = std::time::Instant::now();
+            while self.received_data.lock().unwrap().len() < expected
+                && start.elapsed() < Duration::from_secs(2)
+            {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            // Give the thread time to send more than expected
+            std::thread::sleep(Duration::from_millis(100));
+            self.received_data.lock().unwrap().len()
+        }
+
+        async fn stop(self) {
+            self.termination_signal.store(true, Ordering::SeqCst);
+            self.handle.await.unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rotation_drains_previous_file() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let thread = ReadThread::start(checkpoints.clone());
+
+        let log_path = create_test_file(&temp_path, "0.log")?;
+        assert_eq!(thread.send_and_wait(&log_path, 1), 1);
+
+        // The kubelet renames the file after more lines were written and creates a new one
+        write_to_existing_file(&log_path, "This line is written before the rotation.")?;
+        let rotated_path = temp_path.join("0.log.20240101-000000");
+        std::fs::rename(&log_path, &rotated_path)?;
+        create_test_file(&temp_path, "0.log")?;
+
+        // The tail of the rotated file and the new file are uploaded
+        assert_eq!(thread.send_and_wait(&log_path, 3), 3);
+        thread.stop().await;
+
+        let rotated = checkpoints.get(&rotated_path).unwrap();
+        assert_eq!(rotated.offset, std::fs::metadata(&rotated_path)?.len());
+        let current = checkpoints.get(&log_path).unwrap();
+        assert!(current.matches(&std::fs::metadata(&log_path)?));
+        assert_eq!(current.offset, std::fs::metadata(&log_path)?.len());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_rotation_keeps_offset_of_renamed_file() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let thread = ReadThread::start(checkpoints.clone());
+
+        let log_path = create_test_file(&temp_path, "0.log")?;
+        assert_eq!(thread.send_and_wait(&log_path, 1), 1);
+
+        // Events for the renamed file do not read it again from the start
+        let rotated_path = temp_path.join("0.log.20240101-000000");
+        std::fs::rename(&log_path, &rotated_path)?;
+        assert_eq!(thread.send_and_wait(&rotated_path, 1), 1);
+        assert!(checkpoints.get(&log_path).is_none());
+
+        write_to_existing_file(&rotated_path, "This line is written after the rotation.")?;
+        assert_eq!(thread.send_and_wait(&rotated_path, 2), 2);
+        thread.stop().await;
+
+        let rotated = checkpoints.get(&rotated_path).unwrap();
+        assert_eq!(rotated.offset, std::fs::metadata(&rotated_path)?.len());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_truncation_reads_from_start() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let thread = ReadThread::start(checkpoints.clone());
+
+        let log_path = create_test_file(&temp_path, "0.log")?;
+        assert_eq!(thread.send_and_wait(&log_path, 1), 1);
+
+        // copytruncate keeps the inode but the file is smaller than the offset
+        std::fs::write(&log_path, "short\n")?;
+        assert_eq!(thread.send_and_wait(&log_path, 2), 2);
+        thread.stop().await;
+
+        assert_eq!(checkpoints.get(&log_path).unwrap().offset, 6);
+        Ok(())
+    }
+
+    fn compress_file(path: &Path) -> Result<PathBuf, std::io::Error> {
+        let compressed_path = PathBuf::from(format!("{}.gz", path.display()));
+        let mut encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&compressed_path)?,
+            flate2::Compression::default(),
+        );
+        std::io::copy(&mut std::fs::File::open(path)?, &mut encoder)?;
+        encoder.finish()?;
+        std::fs::remove_file(path)?;
+        Ok(compressed_path)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_compressed_file_resumes_from_rotated_offset() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let options = ReadOptions {
+            ship_compressed: true,
+            ..Default::default()
+        };
+        let thread = ReadThread::start_with_options(checkpoints.clone(), options);
+
+        let rotated_path = create_test_file(&temp_path, "0.log.20240101-000000")?;
+        assert_eq!(thread.send_and_wait(&rotated_path, 1), 1);
+        let shipped = checkpoints.get(&rotated_path).unwrap().offset;
+
+        // Lines written before the compression were not shipped yet
+        write_to_existing_file(&rotated_path, "This line was not shipped.")?;
+        let uncompressed_len = std::fs::metadata(&rotated_path)?.len();
+        let compressed_path = compress_file(&rotated_path)?;
+        assert_eq!(thread.send_and_wait(&compressed_path, 2), 2);
+
+        // The compressed file is not shipped again
+        assert_eq!(thread.send_and_wait(&compressed_path, 3), 2);
+        thread.stop().await;
+
+        let compressed = checkpoints.get(&compressed_path).unwrap();
+        assert!(compressed.offset > shipped);
+        assert_eq!(compressed.offset, uncompressed_len);
+        assert!(checkpoints.get(&rotated_path).is_none());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_compressed_file_drains_rotation_while_stopped() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoint_path = temp_path.join("checkpoints.json");
+        let log_path = create_test_file(&temp_path, "0.log")?;
+
+        // logd ships the file and stops
+        let checkpoints = CheckpointStore::load(&checkpoint_path, Duration::from_secs(60))?;
+        let thread = ReadThread::start(checkpoints.clone());
+        assert_eq!(thread.send_and_wait(&log_path, 1), 1);
+        thread.stop().await;
+
+        // The file is rotated and compressed while logd is not running
+        write_to_existing_file(&log_path, "This line was not shipped.")?;
+        let rotated_path = temp_path.join("0.log.20240101-000000");
+        std::fs::rename(&log_path, &rotated_path)?;
+        create_test_file(&temp_path, "0.log")?;
+        let compressed_path = compress_file(&rotated_path)?;
+
+        // The compressed tail is drained before the new file
+        let checkpoints = CheckpointStore::load(&checkpoint_path, Duration::from_secs(60))?;
+        let options = ReadOptions {
+            ship_compressed: true,
+            ..Default::default()
+        };
+        let thread = ReadThread::start_with_options(checkpoints.clone(), options);
+        assert_eq!(thread.send_and_wait(&log_path, 2), 2);
+        assert_eq!(thread.send_and_wait(&compressed_path, 3), 2);
+        thread.stop().await;
+
+        assert!(checkpoints.get(&compressed_path).is_some());
+        let current = checkpoints.get(&log_path).unwrap();
+        assert!(current.matches(&std::fs::metadata(&log_path)?));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_docker_layout_reads_only_json_logs() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let thread = ReadThread::start(checkpoints.clone());
+
+        // /var/lib/docker/containers/<id>/<id>-json.log
+        let container_id = "f".repeat(64);
+        let container_path = temp_path.join(&container_id);
+        std::fs::create_dir(&container_path)?;
+        let config_path = create_test_file(&container_path, "config.v2.json")?;
+        let log_path = container_path.join(format!("{container_id}-json.log"));
+        std::fs::write(
+            &log_path,
+            "{\"log\":\"Hello, world!\\n\",\"stream\":\"stdout\",\"time\":\"2019-08-14T09:46:13Z\"}\n",
+        )?;
+
+        assert_eq!(thread.send_and_wait(&config_path, 1), 0);
+        assert_eq!(thread.send_and_wait(&log_path, 1), 1);
+        thread.stop().await;
+
+        assert!(checkpoints.get(&config_path).is_none());
+        assert!(checkpoints.get(&log_path).is_some());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_multiline_record_is_flushed_after_timeout() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let options = ReadOptions {
+            output_format: OutputFormat::Structured,
+            multiline: MultilinePattern::from_presets("java").ok(),
+            flush_timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let thread = ReadThread::start_with_options(checkpoints.clone(), options);
+
+        let log_path = temp_path.join("0.log");
+        std::fs::write(
+            &log_path,
+            "2016-10-06T00:17:09Z stderr F java.lang.RuntimeException: boom\n\
+             2016-10-06T00:17:09Z stderr F \tat com.example.Main.main(Main.java:5)\n",
+        )?;
+
+        // The trace is held back until no line continued it within the flush timeout
+        assert_eq!(thread.send_and_wait(&log_path, 1), 1);
+        thread.stop().await;
+
+        let log_len = std::fs::metadata(&log_path)?.len();
+        assert_eq!(checkpoints.get(&log_path).unwrap().offset, log_len);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_dedup_window_outlasts_flush_timeout() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let options = ReadOptions {
+            output_format: OutputFormat::Structured,
+            flush_timeout: Duration::from_millis(100),
+            dedup: Some(DedupOptions {
+                mode: "exact".parse().unwrap(),
+                window: Duration::from_secs(60),
+            }),
+            ..Default::default()
+        };
+        let thread = ReadThread::start_with_options(checkpoints.clone(), options);
+
+        // The repeats span more than the flush timeout but stay within the dedup window
+        let log_path = temp_path.join("0.log");
+        std::fs::write(&log_path, "2016-10-06T00:17:09Z stdout F retrying\n")?;
+        assert_eq!(thread.send_and_wait(&log_path, 1), 0);
+        std::thread::sleep(Duration::from_millis(300));
+        write_to_existing_file(&log_path, "2016-10-06T00:17:10Z stdout F retrying")?;
+        assert_eq!(thread.send_and_wait(&log_path, 1), 0);
+        std::thread::sleep(Duration::from_millis(300));
+        let repeats_len = std::fs::metadata(&log_path)?.len();
+
+        // A different record ends the repeats, which are shipped as one record
+        write_to_existing_file(&log_path, "2016-10-06T00:17:11Z stdout F connected")?;
+        assert_eq!(thread.send_and_wait(&log_path, 1), 1);
+        assert_eq!(checkpoints.get(&log_path).unwrap().offset, repeats_len);
+        thread.stop().await;
+        Ok(())
+    }
+
+    #[test]
+    fn test_upload_metadata_from_pod_log_path() {
+        let path = Path::new(
+            "/var/log/pods/kube-system_coredns-5d78c9869d-x2vbn_1f5c6f3e-2a4b-4c5d-8e9f-0a1b2c3d4e5f/coredns/2.log.20240101-000000.gz",
+        );
+        let source = PodLogSource::from_path(path).unwrap();
+        assert_eq!(source.namespace, "kube-system");
+        assert_eq!(source.pod_name, "coredns-5d78c9869d-x2vbn");
+        assert_eq!(source.pod_uid, "1f5c6f3e-2a4b-4c5d-8e9f-0a1b2c3d4e5f");
+        assert_eq!(source.container_name, "coredns");
+        assert_eq!(source.restart_count, 2);
+
+        let metadata = UploadMetadata {
+            path: path.parent().unwrap().to_str().unwrap().to_string(),
+            file: "2.log.20240101-000000.gz".to_string(),
+            format: "raw".to_string(),
+            encoding: None,
+            source: Some(source),
+            pod: None,
+        };
+        let value = serde_json::to_value(metadata).unwrap();
+        assert_eq!(value["namespace"], "kube-system");
+        assert_eq!(value["restart_count"], 2);
+        assert_eq!(value["file"], "2.log.20240101-000000.gz");
+    }
+
+    #[test]
+    fn test_upload_metadata_unknown_layout() {
+        // docker json-file layout
+        let container_id = "f".repeat(64);
+        let path = PathBuf::from(format!(
+            "/var/lib/docker/containers/{container_id}/{container_id}-json.log"
+        ));
+        assert!(PodLogSource::from_path(&path).is_none());
+        assert!(
+            PodLogSource::from_path(Path::new("/var/log/pods/default_web/app/0.log")).is_none()
+        );
+        assert!(PodLogSource::from_path(Path::new("/tmp/0.log")).is_none());
+
+        let metadata = UploadMetadata {
+            path: "/tmp".to_string(),
+            file: "0.log".to_string(),
+            format: "raw".to_string(),
+            encoding: None,
+            source: None,
+            pod: None,
+        };
+        assert_eq!(
+            serde_json::to_value(metadata).unwrap(),
+            serde_json::json!({"path": "/tmp", "file": "0.log", "format": "raw"})
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_pod_annotation_disables_logs() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": "web",
+                "namespace": "default",
+                "uid": "4a8e9c55",
+                "annotations": {"hik8s.ai/logs": "disabled"}
+            }
+        }))
+        .unwrap();
+        let pods = PodCache::default();
+        pods.apply(&pod);
+        let options = ReadOptions {
+            pod_annotations: true,
+            ..Default::default()
+        };
+        let thread = ReadThread::start_with_pods(checkpoints.clone(), options, pods.clone());
+
+        let container_path = temp_path.join("default_web_4a8e9c55/web");
+        std::fs::create_dir_all(&container_path)?;
+        let log_path = create_test_file(&container_path, "0.log")?;
+        assert_eq!(thread.send_and_wait(&log_path, 1), 0);
+
+        // The lines written while logs were disabled are not shipped later
+        let log_len = std::fs::metadata(&log_path)?.len();
+        assert_eq!(checkpoints.get(&log_path).unwrap().offset, log_len);
+        pods.delete(&pod);
+        write_to_existing_file(&log_path, "This line is shipped.")?;
+        assert_eq!(thread.send_and_wait(&log_path, 1), 1);
+        thread.stop().await;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_files_wait_for_pod_list() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": "web",
+                "namespace": "default",
+                "uid": "4a8e9c55",
+                "annotations": {"hik8s.ai/logs": "disabled"}
+            }
+        }))
+        .unwrap();
+        let pods = PodCache::unlisted();
+        let options = ReadOptions {
+            pod_annotations: true,
+            ..Default::default()
+        };
+        let thread = ReadThread::start_with_pods(checkpoints.clone(), options, pods.clone());
+
+        let disabled_path = temp_path.join("default_web_4a8e9c55/web");
+        std::fs::create_dir_all(&disabled_path)?;
+        let disabled_log = create_test_file(&disabled_path, "0.log")?;
+        let api_path = temp_path.join("default_api_6c1f0f6e/api");
+        std::fs::create_dir_all(&api_path)?;
+        let api_log = create_test_file(&api_path, "0.log")?;
+        assert_eq!(thread.send_and_wait(&disabled_log, 1), 0);
+        assert_eq!(thread.send_and_wait(&api_log, 1), 0);
+
+        // Once listed, the waiting files are read with the settings of their pods
+        pods.replace(&[pod]);
+        assert_eq!(thread.wait(1), 1);
+        let disabled_len = std::fs::metadata(&disabled_log)?.len();
+        assert_eq!(checkpoints.get(&disabled_log).unwrap().offset, disabled_len);
+        thread.stop().await;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_read_file_and_send_data_drains_deleted_file() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let file_path = create_test_file(&temp_path, "0.log")?;
+
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let thread = ReadThread::start(checkpoints.clone());
+        assert_eq!(thread.send_and_wait(&file_path, 1), 1);
+
+        // Lines written right before the file is deleted are read from the open handle
+        write_to_existing_file(&file_path, "This is the last line.")?;
+        std::fs::remove_file(&file_path)?;
+        assert_eq!(thread.send_and_wait(&file_path, 2), 2);
+        assert!(checkpoints.get(&file_path).is_none());
+
+        thread.stop().await;
+        Ok(())
+    }
+
+    // Accepts one request at a time after a delay, and records the committed offset of the
+    // file when each request arrives
+    struct SlowClient(Arc<SlowUploads>);
+
+    struct SlowUploads {
+        checkpoints: CheckpointStore,
+        path: PathBuf,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        offsets: Mutex<Vec<u64>>,
+    }
+
+    impl Client for SlowClient {
+        async fn send_multipart_request(
+            &self,
+            _route: &str,
+            _form_data: reqwest::multipart::Form,
+        ) -> Result<(), Hik8sClientError> {
+            let uploads = &self.0;
+            let in_flight = uploads.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            uploads.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            let offset = uploads
+                .checkpoints
+                .get(&uploads.path)
+                .map_or(0, |c| c.offset);
+            uploads.offsets.lock().unwrap().push(offset);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            uploads.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_slow_upload_pauses_reading() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("0.log");
+        let line = format!(
+            "2024-01-01T00:00:00.000000000Z stdout F {}\n",
+            "x".repeat(60)
+        );
+        std::fs::write(&path, line.repeat(400))?;
+        let file_len = std::fs::metadata(&path)?.len();
+
+        let checkpoints = CheckpointStore::load(
+            &temp_dir.path().join("checkpoints.json"),
+            Duration::from_secs(60),
+        )?;
+        let uploads = Arc::new(SlowUploads {
+            checkpoints: checkpoints.clone(),
+            path: path.clone(),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+            offsets: Mutex::new(Vec::new()),
+        });
+        let batch_size = 4096;
+        let options = ReadOptions {
+            batch_size,
+            ..Default::default()
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let termination_signal = Arc::new(AtomicBool::new(false));
+        let termination_signal_clone = Arc::clone(&termination_signal);
+        let client = SlowClient(Arc::clone(&uploads));
+        // The read loop blocks on the channel, it runs on the blocking pool like in main
+        let runtime = tokio::runtime::Handle::current();
+        let handle = tokio::task::spawn_blocking(move || {
+            runtime.block_on(read_file_and_send_data(
+                receiver,
+                client,
+                checkpoints,
+                options,
+                PodCache::default(),
+                Heartbeat::default(),
+                termination_signal_clone,
+            ))
+        });
+        sender.send(HashSet::from([path.clone()])).unwrap();
+        let start = std::time::Instant::now();
+        while uploads.checkpoints.get(&path).map_or(0, |c| c.offset) < file_len
+            && start.elapsed() < Duration::from_secs(5)
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        termination_signal.store(true, Ordering::SeqCst);
+        handle.await.unwrap()?;
+
+        // Each request waited for the previous one, and at most one batch was read ahead of
+        // the committed offset. A batch ends with the line that fills it.
+        assert_eq!(uploads.max_in_flight.load(Ordering::SeqCst), 1);
+        let mut offsets = uploads.offsets.lock().unwrap().clone();
+        offsets.push(file_len);
+        assert_eq!(offsets[0], 0);
+        let max_chunk = (batch_size + line.len()) as u64;
+        assert!(offsets
+            .windows(2)
+            .all(|w| w[1] > w[0] && w[1] - w[0] <= max_chunk));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_byte_budget_pauses_reading() -> Result<(), ReadThreadError> {
+        setup_tracing()?;
+        let temp_dir = tempdir()?;
+        let temp_path = temp_dir.path().to_path_buf();
+        let checkpoints =
+            CheckpointStore::load(&temp_path.join("checkpoints.json"), Duration::from_secs(60))?;
+        let budget = ByteBudget::new(4096);
+        let options = ReadOptions {
+            budget: budget.clone(),
+            ..Default::default()
+        };
+        let thread = ReadThread::start_with_options(checkpoints.clone(), options);
+
+        // Another chunk holds the whole budget
+        let permit = budget.acquire(4096).await;
+        let log_path = create_test_file(&temp_path, "0.log")?;
+        assert_eq!(thread.send_and_wait(&log_path, 1), 0);
+        assert!(checkpoints.get(&log_path).is_none());
+
+        drop(permit);
+        assert_eq!(thread.wait(1), 1);
+        let log_len = std::fs::metadata(&log_path)?.len();
+        assert_eq!(checkpoints.get(&log_path).unwrap().offset, log_len);
+        assert_eq!(budget.available(), 4096);
+        thread.stop().await;
         Ok(())
     }
 }
@@ -1,9 +1,9 @@
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-pub fn create_test_file(dir_path: &PathBuf, file_name: &str) -> Result<PathBuf, std::io::Error> {
-    let file_path = dir_path.join(format!("{}", file_name));
+pub fn create_test_file(dir_path: &Path, file_name: &str) -> Result<PathBuf, std::io::Error> {
+    let file_path = dir_path.join(file_name);
     let contents = format!(
         "This is the first line of {}.\nThis is the second line of {}.",
         file_name, file_name
@@ -12,11 +12,8 @@ pub fn create_test_file(dir_path: &PathBuf, file_name: &str) -> Result<PathBuf,
     writeln!(file, "{}", contents)?;
     Ok(file_path)
 }
-pub fn write_to_existing_file(file_path: &PathBuf, content: &str) -> Result<(), std::io::Error> {
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open(file_path)?;
+pub fn write_to_existing_file(file_path: &Path, content: &str) -> Result<(), std::io::Error> {
+    let mut file = OpenOptions::new().append(true).open(file_path)?;
     writeln!(file, "{}", content)?;
     Ok(())
 }
@@ -1,10 +1,15 @@
 use crate::env::get_env_var;
+use crate::health::{Health, READY_AUTH_TOKEN, READY_CAUGHT_UP};
 use reqwest::header::AUTHORIZATION;
 use reqwest::{multipart::Form, Client};
+use std::time::Duration;
+use tracing::warn;
 
 use super::auth::Auth;
 use super::Hik8sClientError;
 
+const AUTH_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(Clone)]
 pub struct Hik8sClient {
     pub client: Client,
@@ -13,6 +18,8 @@ pub struct Hik8sClient {
     host: String,
     port: String,
     auth: Auth,
+    // marks the auth token and the first successful request for readiness
+    health: Option<Health>,
 }
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
@@ -34,8 +41,41 @@ impl Hik8sClient {
             host,
             port,
             auth,
+            health: None,
         })
     }
+
+    // The daemon is ready once a token was acquired and it caught up
+    pub fn with_health(mut self, health: Health) -> Self {
+        health.require(READY_AUTH_TOKEN);
+        health.require(READY_CAUGHT_UP);
+        self.health = Some(health);
+        self
+    }
+
+    fn mark(&self, condition: &'static str) {
+        if let Some(health) = &self.health {
+            health.mark(condition);
+        }
+    }
+
+    // Acquires the token at startup, so readiness does not wait for the first request.
+    // Retried until it succeeds.
+    pub async fn authenticate(&self) {
+        loop {
+            match self.auth.get_auth0_token().await {
+                Ok(_) => {
+                    self.mark(READY_AUTH_TOKEN);
+                    return;
+                }
+                Err(e) => {
+                    warn!("Failed to acquire an auth token, retrying: {e}");
+                    tokio::time::sleep(AUTH_RETRY_INTERVAL).await;
+                }
+            }
+        }
+    }
+
     pub fn get_uri(&self, route: &str) -> String {
         let protocol = if self.insecure { "http" } else { "https" };
         format!("{}://{}:{}/{route}", protocol, self.host, self.port)
@@ -46,6 +86,7 @@ impl Hik8sClient {
         form: Form,
     ) -> Result<(), Hik8sClientError> {
         let token = self.auth.get_auth0_token().await?;
+        self.mark(READY_AUTH_TOKEN);
 
         self.client
             .post(self.get_uri(route))
@@ -54,6 +95,7 @@ impl Hik8sClient {
             .send()
             .await?
             .error_for_status()?;
+        self.mark(READY_CAUGHT_UP);
         Ok(())
     }
     pub async fn send_request(
@@ -62,6 +104,7 @@ impl Hik8sClient {
         json: &serde_json::Value,
     ) -> Result<(), Hik8sClientError> {
         let token = self.auth.get_auth0_token().await?;
+        self.mark(READY_AUTH_TOKEN);
 
         self.client_with_middleware
             .post(self.get_uri(route))
@@ -70,6 +113,7 @@ impl Hik8sClient {
             .send()
             .await?
             .error_for_status()?;
+        self.mark(READY_CAUGHT_UP);
         Ok(())
     }
 }
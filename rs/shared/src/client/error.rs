@@ -16,4 +16,36 @@ pub enum Hik8sClientError {
     AuthError(#[from] AuthError),
     #[error("Json serialize error: {0}")]
     JsonError(#[from] serde_json::Error),
+    #[error("Mock client error")]
+    MockError,
+    #[error("Mock client status: {0}")]
+    MockStatus(reqwest::StatusCode),
+}
+
+impl Hik8sClientError {
+    // Status code of a request the server rejected
+    pub fn status(&self) -> Option<reqwest::StatusCode> {
+        match self {
+            Self::ReqwestError(e) => e.status(),
+            Self::ReqwestMiddlewareError(e) => e.status(),
+            Self::MockStatus(status) => Some(*status),
+            _ => None,
+        }
+    }
+
+    // The server rejected the request itself, e.g. 400 or 413, so it fails again when it is
+    // retried. Auth errors, timeouts and throttling are retried.
+    pub fn is_permanent(&self) -> bool {
+        use reqwest::StatusCode;
+        self.status().is_some_and(|status| {
+            status.is_client_error()
+                && ![
+                    StatusCode::UNAUTHORIZED,
+                    StatusCode::FORBIDDEN,
+                    StatusCode::REQUEST_TIMEOUT,
+                    StatusCode::TOO_MANY_REQUESTS,
+                ]
+                .contains(&status)
+        })
+    }
 }
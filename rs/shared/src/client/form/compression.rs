@@ -0,0 +1,102 @@
+use flate2::write::GzEncoder;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+const ZSTD_LEVEL: i32 = 3;
+
+// Codec of the stream part of a multipart upload
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    // Value of the Content-Encoding header of the stream part
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gzip"),
+            Self::Zstd => Some("zstd"),
+        }
+    }
+
+    // Each chunk is a complete gzip member or zstd frame. Concatenated members and frames
+    // decompress to the concatenated chunks.
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, io::Error> {
+        match self {
+            Self::None => Ok(data.to_vec()),
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Self::Zstd => zstd::encode_all(data, ZSTD_LEVEL),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.content_encoding().unwrap_or("none"))
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "none" | "" => Ok(Self::None),
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            other => Err(format!("unknown compression: {other}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::read::MultiGzDecoder;
+    use std::io::Read;
+
+    use super::Compression;
+
+    #[test]
+    fn test_compression_roundtrip() {
+        let first = b"2016-10-06T00:17:09Z stdout F Hello, world!\n".repeat(100);
+        let second = b"2016-10-06T00:17:10Z stdout F Goodbye, world!\n".repeat(100);
+
+        let gzip = [
+            Compression::Gzip.compress(&first).unwrap(),
+            Compression::Gzip.compress(&second).unwrap(),
+        ]
+        .concat();
+        assert!(gzip.len() < first.len());
+        let mut data = Vec::new();
+        MultiGzDecoder::new(gzip.as_slice())
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, [first.clone(), second.clone()].concat());
+
+        let zstd = [
+            Compression::Zstd.compress(&first).unwrap(),
+            Compression::Zstd.compress(&second).unwrap(),
+        ]
+        .concat();
+        assert!(zstd.len() < first.len());
+        let data = zstd::decode_all(zstd.as_slice()).unwrap();
+        assert_eq!(data, [first, second].concat());
+    }
+
+    #[test]
+    fn test_compression_from_str() {
+        assert_eq!("gzip".parse(), Ok(Compression::Gzip));
+        assert_eq!(" ZSTD".parse(), Ok(Compression::Zstd));
+        assert_eq!("none".parse(), Ok(Compression::None));
+        assert!("brotli".parse::<Compression>().is_err());
+    }
+}
@@ -1,18 +1,33 @@
 use bytes::Bytes;
 use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_ENCODING},
     multipart::{Form, Part},
     Body,
 };
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 
+use super::compression::Compression;
 use super::error::FormDataError;
 
 pub fn create_form_data(
     metadata: serde_json::Value,
-    stream: UnboundedReceiverStream<Result<Bytes, hyper::Error>>,
+    stream: ReceiverStream<Result<Bytes, hyper::Error>>,
+    compression: Compression,
 ) -> Result<Form, FormDataError> {
     let metadata = Part::text(metadata.to_string()).mime_str("application/json")?;
-    let stream = Part::stream(Body::wrap_stream(stream)).mime_str("application/octet-stream")?;
+    let stream = match compression.content_encoding() {
+        None => Part::stream(Body::wrap_stream(stream)),
+        Some(encoding) => {
+            let stream = stream.map(move |chunk| match chunk {
+                Ok(bytes) => compression.compress(&bytes).map(Bytes::from),
+                Err(e) => Err(std::io::Error::other(e)),
+            });
+            let headers =
+                HeaderMap::from_iter([(CONTENT_ENCODING, HeaderValue::from_static(encoding))]);
+            Part::stream(Body::wrap_stream(stream)).headers(headers)
+        }
+    }
+    .mime_str("application/octet-stream")?;
 
     let form_data = Form::new()
         .part("metadata", metadata)
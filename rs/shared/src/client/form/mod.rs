@@ -1,5 +1,7 @@
+mod compression;
 mod error;
 mod form;
 
+pub use compression::Compression;
 pub use error::FormDataError;
 pub use form::create_form_data;
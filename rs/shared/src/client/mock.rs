@@ -1,15 +1,34 @@
 use reqwest::multipart::Form;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use super::{Client, Hik8sClientError};
 
 pub struct MockHik8sClient {
     received_data: Arc<Mutex<Vec<Form>>>,
+    failures: Arc<AtomicUsize>,
+    failure_status: Option<reqwest::StatusCode>,
 }
 
 impl MockHik8sClient {
     pub fn new(received_data: Arc<Mutex<Vec<Form>>>) -> Self {
-        MockHik8sClient { received_data }
+        MockHik8sClient {
+            received_data,
+            failures: Arc::new(AtomicUsize::new(0)),
+            failure_status: None,
+        }
+    }
+
+    // The next `failures` requests are rejected without receiving data
+    pub fn with_failures(self, failures: usize) -> Self {
+        self.failures.store(failures, Ordering::SeqCst);
+        self
+    }
+
+    // Failed requests are rejected with this status
+    pub fn with_failure_status(mut self, status: reqwest::StatusCode) -> Self {
+        self.failure_status = Some(status);
+        self
     }
 }
 
@@ -19,6 +38,16 @@ impl Client for MockHik8sClient {
         _route: &str,
         form_data: Form,
     ) -> Result<(), Hik8sClientError> {
+        if self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+        {
+            return Err(match self.failure_status {
+                Some(status) => Hik8sClientError::MockStatus(status),
+                None => Hik8sClientError::MockError,
+            });
+        }
         // received data is evalued in the test
         let mut data = self.received_data.lock().unwrap();
         data.push(form_data);
@@ -0,0 +1,7 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HealthError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
@@ -0,0 +1,257 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tracing::info;
+
+use super::HealthError;
+
+// Readiness conditions marked by the client. The daemon is caught up after a request
+// succeeded, or once it found nothing to send.
+pub const READY_AUTH_TOKEN: &str = "auth_token";
+pub const READY_CAUGHT_UP: &str = "caught_up";
+
+// Readiness conditions and liveness heartbeats of a daemon, served on /readyz and /livez.
+// /healthz reports both.
+#[derive(Clone, Default)]
+pub struct Health {
+    state: Arc<Mutex<HealthState>>,
+}
+
+#[derive(Default)]
+struct HealthState {
+    // condition and whether it was met
+    conditions: BTreeMap<&'static str, bool>,
+    components: BTreeMap<String, Component>,
+}
+
+struct Component {
+    last_beat: Instant,
+    // stale if it did not beat for this long, only alive while the heartbeat exists if unset
+    timeout: Option<Duration>,
+    stopped: bool,
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HealthState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // The daemon is not ready before the condition is marked
+    pub fn require(&self, condition: &'static str) {
+        self.lock().conditions.entry(condition).or_insert(false);
+    }
+
+    pub fn mark(&self, condition: &'static str) {
+        self.lock().conditions.insert(condition, true);
+    }
+
+    // Registers a component that is alive while it beats. It counts as dead once the
+    // heartbeat is dropped, e.g. when its task exits.
+    pub fn heartbeat(&self, name: &str, timeout: Option<Duration>) -> Heartbeat {
+        self.lock().components.insert(
+            name.to_string(),
+            Component {
+                last_beat: Instant::now(),
+                timeout,
+                stopped: false,
+            },
+        );
+        Heartbeat {
+            health: self.clone(),
+            name: name.to_string(),
+        }
+    }
+
+    // Returns the conditions that are not met
+    pub fn readiness(&self) -> Result<(), Vec<String>> {
+        let unmet: Vec<String> = self
+            .lock()
+            .conditions
+            .iter()
+            .filter(|(_, met)| !**met)
+            .map(|(condition, _)| condition.to_string())
+            .collect();
+        if unmet.is_empty() {
+            Ok(())
+        } else {
+            Err(unmet)
+        }
+    }
+
+    // Returns the components that stopped or did not beat in time
+    pub fn liveness(&self) -> Result<(), Vec<String>> {
+        let dead: Vec<String> = self
+            .lock()
+            .components
+            .iter()
+            .filter(|(_, component)| {
+                component.stopped
+                    || component
+                        .timeout
+                        .is_some_and(|timeout| component.last_beat.elapsed() > timeout)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        if dead.is_empty() {
+            Ok(())
+        } else {
+            Err(dead)
+        }
+    }
+
+    // Routes of the probes, daemons can add their own routes before serving it
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .route("/livez", get(livez))
+            .with_state(self.clone())
+    }
+}
+
+// Reports to the health of its daemon while it exists
+#[derive(Default)]
+pub struct Heartbeat {
+    health: Health,
+    name: String,
+}
+
+impl Heartbeat {
+    // Components that know when the daemon is ready mark it through their heartbeat
+    pub fn mark(&self, condition: &'static str) {
+        self.health.mark(condition);
+    }
+
+    pub fn beat(&self) {
+        if let Some(component) = self.health.lock().components.get_mut(&self.name) {
+            component.last_beat = Instant::now();
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        if let Some(component) = self.health.lock().components.get_mut(&self.name) {
+            component.stopped = true;
+        }
+    }
+}
+
+async fn healthz(State(health): State<Health>) -> (StatusCode, String) {
+    let mut failures = Vec::new();
+    if let Err(unmet) = health.readiness() {
+        failures.push(format!("not ready: {}", unmet.join(", ")));
+    }
+    if let Err(dead) = health.liveness() {
+        failures.push(format!("not live: {}", dead.join(", ")));
+    }
+    respond(failures)
+}
+
+async fn readyz(State(health): State<Health>) -> (StatusCode, String) {
+    match health.readiness() {
+        Ok(()) => respond(Vec::new()),
+        Err(unmet) => respond(vec![format!("not ready: {}", unmet.join(", "))]),
+    }
+}
+
+async fn livez(State(health): State<Health>) -> (StatusCode, String) {
+    match health.liveness() {
+        Ok(()) => respond(Vec::new()),
+        Err(dead) => respond(vec![format!("not live: {}", dead.join(", "))]),
+    }
+}
+
+fn respond(failures: Vec<String>) -> (StatusCode, String) {
+    if failures.is_empty() {
+        (StatusCode::OK, "ok\n".to_string())
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("{}\n", failures.join("\n")),
+        )
+    }
+}
+
+// Serves the router on all interfaces until the process exits
+pub async fn serve(port: u16, router: Router) -> Result<(), HealthError> {
+    let address = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+    let listener = TcpListener::bind(address).await?;
+    info!("Serving health endpoints on {address}");
+    axum::serve(listener, router).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_waits_for_required_conditions() {
+        let health = Health::new();
+        assert!(health.readiness().is_ok());
+
+        health.require(READY_AUTH_TOKEN);
+        health.require(READY_CAUGHT_UP);
+        health.mark(READY_AUTH_TOKEN);
+        assert_eq!(health.readiness(), Err(vec![READY_CAUGHT_UP.to_string()]));
+
+        health.mark(READY_CAUGHT_UP);
+        // requiring it again does not reset it
+        health.require(READY_CAUGHT_UP);
+        assert!(health.readiness().is_ok());
+    }
+
+    #[test]
+    fn test_liveness_follows_heartbeats() {
+        let health = Health::new();
+        let reader = health.heartbeat("reader", Some(Duration::from_millis(50)));
+        let watcher = health.heartbeat("watcher", None);
+        assert!(health.liveness().is_ok());
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(health.liveness(), Err(vec!["reader".to_string()]));
+        reader.beat();
+        assert!(health.liveness().is_ok());
+
+        drop(watcher);
+        assert_eq!(health.liveness(), Err(vec!["watcher".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_serve_probes() -> Result<(), HealthError> {
+        let health = Health::new();
+        health.require(READY_CAUGHT_UP);
+        let _heartbeat = health.heartbeat("reader", None);
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let address = listener.local_addr()?;
+        let router = health.router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let get = |path: &str| reqwest::get(format!("http://{address}{path}"));
+        let response = get("/readyz").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.text().await.unwrap(), "not ready: caught_up\n");
+        assert_eq!(get("/livez").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(
+            get("/healthz").await.unwrap().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        health.mark(READY_CAUGHT_UP);
+        assert_eq!(get("/readyz").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(get("/healthz").await.unwrap().status(), StatusCode::OK);
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+mod error;
+mod health;
+
+pub use error::HealthError;
+pub use health::{serve, Health, Heartbeat, READY_AUTH_TOKEN, READY_CAUGHT_UP};
@@ -1,3 +1,7 @@
+#![allow(clippy::module_inception)]
+
 pub mod client;
 pub mod env;
+pub mod health;
+pub mod metrics;
 pub mod tracing;
@@ -1,5 +1,13 @@
+use std::time::Duration;
+
 pub const ROUTE_CUSTOM_RESOURCE: &str = "customresource";
 pub const ROUTE_EVENT: &str = "event";
 pub const ROUTE_RESOURCE: &str = "resource";
 pub const LOCAL_THREAD_LIMIT: usize = 8;
+pub const HEALTH_PORT: u16 = 8080;
+pub const ENV_HEALTH_PORT: &str = "WATCHD_HEALTH_PORT";
 pub const CLUSTER_ROLE_NAME: &str = "api-resources-reader";
+// A watcher beats on every event and on every tick while its last list or watch succeeded,
+// it is not live once it did not beat for the heartbeat timeout
+pub const WATCHER_TICK: Duration = Duration::from_secs(60);
+pub const WATCHER_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(15 * 60);
@@ -7,6 +7,7 @@ use k8s_openapi::api::{
 };
 use kube::Api;
 use shared::client::Hik8sClient;
+use shared::health::Health;
 use std::fmt;
 
 use crate::{
@@ -84,25 +85,33 @@ impl KubeApiResource {
         }
     }
 
-    pub async fn setup_watcher(self, client: Hik8sClient) -> Result<(), WatchDaemonError> {
+    pub async fn setup_watcher(
+        self,
+        client: Hik8sClient,
+        health: Health,
+    ) -> Result<(), WatchDaemonError> {
         let route = self.route();
         let name = self.to_string();
         match self {
-            Self::Event(api) => setup_watcher(name, api, client, route, true).await,
-            Self::Deployment(api) => setup_watcher(name, api, client, route, true).await,
-            Self::DaemonSet(api) => setup_watcher(name, api, client, route, true).await,
-            Self::ReplicaSet(api) => setup_watcher(name, api, client, route, true).await,
-            Self::StatefulSet(api) => setup_watcher(name, api, client, route, true).await,
-            Self::Pod(api) => setup_watcher(name, api, client, route, true).await,
-            Self::Service(api) => setup_watcher(name, api, client, route, true).await,
-            Self::Namespace(api) => setup_watcher(name, api, client, route, true).await,
-            Self::Node(api) => setup_watcher(name, api, client, route, true).await,
-            Self::Ingress(api) => setup_watcher(name, api, client, route, true).await,
-            Self::ServiceAccount(api) => setup_watcher(name, api, client, route, true).await,
-            Self::Role(api) => setup_watcher(name, api, client, route, true).await,
-            Self::ClusterRole(api) => setup_watcher(name, api, client, route, true).await,
-            Self::ClusterRoleBinding(api) => setup_watcher(name, api, client, route, true).await,
-            Self::StorageClass(api) => setup_watcher(name, api, client, route, true).await,
+            Self::Event(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::Deployment(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::DaemonSet(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::ReplicaSet(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::StatefulSet(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::Pod(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::Service(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::Namespace(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::Node(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::Ingress(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::ServiceAccount(api) => {
+                setup_watcher(name, api, client, health, route, true).await
+            }
+            Self::Role(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::ClusterRole(api) => setup_watcher(name, api, client, health, route, true).await,
+            Self::ClusterRoleBinding(api) => {
+                setup_watcher(name, api, client, health, route, true).await
+            }
+            Self::StorageClass(api) => setup_watcher(name, api, client, health, route, true).await,
         }
     }
 }
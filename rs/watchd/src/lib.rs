@@ -2,4 +2,5 @@ pub mod constant;
 pub mod customresource;
 mod error;
 pub mod kubeapi;
+mod metrics;
 pub mod watcher;
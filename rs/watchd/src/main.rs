@@ -1,9 +1,14 @@
 use kube::{api::DynamicObject, Api, Client};
-use shared::{client::Hik8sClient, tracing::setup_tracing};
+use shared::{
+    client::Hik8sClient,
+    env::get_env_var,
+    health::{serve, Health},
+    tracing::setup_tracing,
+};
 use std::{collections::HashMap, error::Error};
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 use watchd::{
-    constant::{CLUSTER_ROLE_NAME, ROUTE_CUSTOM_RESOURCE},
+    constant::{CLUSTER_ROLE_NAME, ENV_HEALTH_PORT, HEALTH_PORT, ROUTE_CUSTOM_RESOURCE},
     customresource::{get_api_resource, list_crds},
     kubeapi::KubeApiResource,
     watcher::setup_watcher,
@@ -13,16 +18,31 @@ use watchd::{
 async fn main() -> Result<(), Box<dyn Error>> {
     setup_tracing()?;
 
+    // Probes and metrics, ready after the first request and live while all watchers receive events
+    let health = Health::new();
+    let health_port = get_env_var(ENV_HEALTH_PORT)
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(HEALTH_PORT);
+    let router = health.router().merge(shared::metrics::router());
+    tokio::spawn(async move {
+        serve(health_port, router)
+            .await
+            .inspect_err(|e| error!("Error: Health server exit: {}", e))
+    });
+
     // Create clients
     let kubeapi_client = Client::try_default().await?;
-    let hik8s_client = Hik8sClient::new(false).unwrap();
+    let hik8s_client = Hik8sClient::new(false).unwrap().with_health(health.clone());
+    let client = hik8s_client.clone();
+    tokio::spawn(async move { client.authenticate().await });
 
     // Setup resource watcher
     let mut failed_resource_names = vec![];
     for resource in KubeApiResource::new_all(&kubeapi_client) {
         let name = resource.to_string();
         resource
-            .setup_watcher(hik8s_client.clone())
+            .setup_watcher(hik8s_client.clone(), health.clone())
             .await
             .inspect_err(|_| failed_resource_names.push(name))
             .ok();
@@ -43,6 +63,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 name_with_group.clone(),
                 dynamic_api,
                 hik8s_client.clone(),
+                health.clone(),
                 ROUTE_CUSTOM_RESOURCE,
                 true,
             )
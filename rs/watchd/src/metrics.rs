@@ -0,0 +1,115 @@
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, GaugeVec, HistogramVec, IntCounterVec, Opts,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Instant;
+
+// Metrics are registered in the default prometheus registry and labeled with the watched
+// kind, or group/plural for custom resources
+
+pub static EVENTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "watchd_events_total",
+        "Watch events received, by kind and event type",
+        &["kind", "type"]
+    )
+    .unwrap()
+});
+
+pub static SEND_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "watchd_send_failures_total",
+        "Events that failed to be sent to the api, by kind",
+        &["kind"]
+    )
+    .unwrap()
+});
+
+pub static SEMAPHORE_WAIT: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "watchd_semaphore_wait_seconds",
+        "Time an event waited for one of the LOCAL_THREAD_LIMIT send slots of its watcher",
+        &["kind"],
+        vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0]
+    )
+    .unwrap()
+});
+
+pub static WATCHER_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "watchd_watcher_errors_total",
+        "Errors of a watcher stream, by kind",
+        &["kind"]
+    )
+    .unwrap()
+});
+
+pub static WATCHER_RESTARTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "watchd_watcher_restarts_total",
+        "Watchers established again after their stream ended, by kind",
+        &["kind"]
+    )
+    .unwrap()
+});
+
+pub static FORBIDDEN_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "watchd_forbidden_errors_total",
+        "Watcher errors with status 403 that are not logged, by kind",
+        &["kind"]
+    )
+    .unwrap()
+});
+
+pub static LAST_EVENT: LazyLock<LastEvent> = LazyLock::new(|| {
+    let last_event = LastEvent::new();
+    prometheus::register(Box::new(last_event.clone())).unwrap();
+    last_event
+});
+
+// Seconds since the last event of each watcher, computed when the metrics are gathered
+#[derive(Clone)]
+pub struct LastEvent {
+    gauge: GaugeVec,
+    seen: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl LastEvent {
+    fn new() -> Self {
+        let opts = Opts::new(
+            "watchd_seconds_since_last_event",
+            "Seconds since a watcher received its last event, by kind",
+        );
+        Self {
+            gauge: GaugeVec::new(opts, &["kind"]).unwrap(),
+            seen: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn record(&self, kind: &str) {
+        if let Ok(mut seen) = self.seen.lock() {
+            seen.insert(kind.to_string(), Instant::now());
+        }
+    }
+}
+
+impl Collector for LastEvent {
+    fn desc(&self) -> Vec<&Desc> {
+        self.gauge.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        if let Ok(seen) = self.seen.lock() {
+            for (kind, last) in seen.iter() {
+                self.gauge
+                    .with_label_values(&[kind])
+                    .set(last.elapsed().as_secs_f64());
+            }
+        }
+        self.gauge.collect()
+    }
+}
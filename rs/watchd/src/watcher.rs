@@ -1,16 +1,23 @@
 use futures::StreamExt;
 use kube::api::ListParams;
 
-use crate::constant::LOCAL_THREAD_LIMIT;
+use crate::constant::{LOCAL_THREAD_LIMIT, WATCHER_HEARTBEAT_TIMEOUT, WATCHER_TICK};
 use crate::error::WatchDaemonError;
+use crate::metrics::{
+    EVENTS, FORBIDDEN_ERRORS, LAST_EVENT, SEMAPHORE_WAIT, SEND_FAILURES, WATCHER_ERRORS,
+    WATCHER_RESTARTS,
+};
 use k8s_openapi::chrono;
 use kube::runtime::watcher::Error as WatcherError;
 use kube::runtime::watcher::{watcher, Event as WatcherEvent};
 use kube::Api;
 use serde::Serialize;
 use shared::client::Hik8sClient;
+use shared::health::Health;
 use std::fmt::Debug;
+use std::pin::pin;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Semaphore;
 use tracing::{debug, error};
 
@@ -18,6 +25,7 @@ pub async fn setup_watcher<T>(
     name: String,
     api: Api<T>,
     hik8s_client: Hik8sClient,
+    health: Health,
     route: &'static str,
     report_deleted: bool,
 ) -> Result<(), WatchDaemonError>
@@ -32,16 +40,51 @@ where
     }
     api.list(&ListParams::default()).await?;
 
-    let watcher = watcher(api, Default::default());
-
     let thread_limit = Arc::new(Semaphore::new(LOCAL_THREAD_LIMIT));
 
+    // Not live if the stream was not polled or kept failing for this long
+    let heartbeat = health.heartbeat(&format!("watcher/{name}"), Some(WATCHER_HEARTBEAT_TIMEOUT));
+
     // Poll the stream to keep the store up-to-date
     tokio::spawn(async move {
-        watcher
-            .for_each(|event| async {
+        let mut ticks = tokio::time::interval(WATCHER_TICK);
+        loop {
+            // The watcher keeps its resource version across errors and bookmarks, it is only
+            // established again if the stream ends, since that lists all resources again
+            let mut stream = pin!(watcher(api.clone(), Default::default()));
+            // false while the watcher fails to list or watch
+            let mut watching = true;
+            loop {
+                // Bookmarks are not passed on, a quiet watcher beats while it is polled
+                let event = tokio::select! {
+                    event = stream.next() => event,
+                    _ = ticks.tick() => {
+                        if watching {
+                            heartbeat.beat();
+                        }
+                        continue;
+                    }
+                };
+                let Some(event) = event else {
+                    break;
+                };
+                match &event {
+                    Ok(_) => {
+                        watching = true;
+                        heartbeat.beat();
+                        LAST_EVENT.record(&name);
+                    }
+                    Err(_) => {
+                        watching = false;
+                        WATCHER_ERRORS.with_label_values(&[&name]).inc();
+                    }
+                }
                 let client = hik8s_client.clone();
+                let start = Instant::now();
                 if let Ok(permit) = thread_limit.clone().acquire_owned().await {
+                    SEMAPHORE_WAIT
+                        .with_label_values(&[&name])
+                        .observe(start.elapsed().as_secs_f64());
                     let name = name.clone();
                     tokio::spawn(async move {
                         match event {
@@ -57,12 +100,12 @@ where
                             }
                             Err(err) => match err {
                                 WatcherError::WatchError(res) => match res.code {
-                                    403 => {}
+                                    403 => FORBIDDEN_ERRORS.with_label_values(&[&name]).inc(),
                                     _ => error!("Error: {} watcher: {:?}", name, res),
                                 },
                                 WatcherError::InitialListFailed(kube_error) => match kube_error {
                                     kube::Error::Api(res) => match res.code {
-                                        403 => {}
+                                        403 => FORBIDDEN_ERRORS.with_label_values(&[&name]).inc(),
                                         _ => error!("Error: {} watcher: {:?}", name, res),
                                     },
                                     err => error!("Error: {} watcher: {:?}", name, err),
@@ -73,8 +116,10 @@ where
                         drop(permit);
                     });
                 }
-            })
-            .await;
+            }
+            debug!("{} watcher stream ended, watching again", name);
+            WATCHER_RESTARTS.with_label_values(&[&name]).inc();
+        }
     });
     Ok(())
 }
@@ -86,10 +131,19 @@ pub async fn handle_event_and_dispatch<T: Serialize>(
     route: &str,
     report_deleted: bool,
 ) {
+    let event_type = match &event {
+        WatcherEvent::Apply(_) => "apply",
+        WatcherEvent::InitApply(_) => "initapply",
+        WatcherEvent::Init => "init",
+        WatcherEvent::InitDone => "initdone",
+        WatcherEvent::Delete(_) => "delete",
+    };
+    EVENTS.with_label_values(&[name, event_type]).inc();
     match event {
         WatcherEvent::Apply(resource) => {
             let json = wrap_kubeapi_data(resource, "apply");
             if let Err(e) = client.send_request(route, &json).await {
+                SEND_FAILURES.with_label_values(&[name]).inc();
                 error!("Failed to handle apply event for resource {name}: {e}");
             }
             debug!("{route}(Apply): {name}");
@@ -97,6 +151,7 @@ pub async fn handle_event_and_dispatch<T: Serialize>(
         WatcherEvent::InitApply(resource) => {
             let json = wrap_kubeapi_data(resource, "initapply");
             if let Err(e) = client.send_request(route, &json).await {
+                SEND_FAILURES.with_label_values(&[name]).inc();
                 error!("Failed to handle init-apply event for resource {name}: {e}");
             }
             debug!("{route}(InitApply): {name}");
@@ -107,6 +162,7 @@ pub async fn handle_event_and_dispatch<T: Serialize>(
             if report_deleted {
                 let json = wrap_kubeapi_data(resource, "delete");
                 if let Err(e) = client.send_request(route, &json).await {
+                    SEND_FAILURES.with_label_values(&[name]).inc();
                     error!("Failed to handle delete event for resource {name}: {e}");
                 }
             }